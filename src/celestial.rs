@@ -0,0 +1,144 @@
+use crate::network_core::SPEED_OF_LIGHT;
+use crate::time::format_duration;
+
+// Mean radii and Earth-centre distances based on NASA planetary fact sheets.
+pub const EARTH_RADIUS_KM: f64 = 6_371.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CelestialBody {
+    pub name: &'static str,
+    pub radius_km: f64,
+    /// Centre-to-centre distances from Earth at closest, average and farthest.
+    pub min_dist_km: f64,
+    pub avg_dist_km: f64,
+    pub max_dist_km: f64,
+}
+
+pub const MOON: CelestialBody = CelestialBody {
+    name: "Moon",
+    radius_km: 1_737.4,
+    min_dist_km: 363_300.0,
+    avg_dist_km: 384_400.0,
+    max_dist_km: 405_500.0,
+};
+
+pub const MARS: CelestialBody = CelestialBody {
+    name: "Mars",
+    radius_km: 3_389.5,
+    min_dist_km: 54_600_000.0,
+    avg_dist_km: 225_000_000.0,
+    max_dist_km: 401_000_000.0,
+};
+
+pub const VENUS: CelestialBody = CelestialBody {
+    name: "Venus",
+    radius_km: 6_051.8,
+    min_dist_km: 38_000_000.0,
+    avg_dist_km: 170_000_000.0,
+    max_dist_km: 261_000_000.0,
+};
+
+pub const JUPITER: CelestialBody = CelestialBody {
+    name: "Jupiter",
+    radius_km: 69_911.0,
+    min_dist_km: 588_500_000.0,
+    avg_dist_km: 778_500_000.0,
+    max_dist_km: 968_100_000.0,
+};
+
+impl CelestialBody {
+    /// Surface-to-surface gap for a given centre-to-centre distance.
+    pub fn surface_distance_km(&self, center_distance_km: f64) -> f64 {
+        center_distance_km - EARTH_RADIUS_KM - self.radius_km
+    }
+
+    /// One-way light time in seconds for a given centre-to-centre distance.
+    pub fn one_way_light_time(&self, center_distance_km: f64) -> f64 {
+        self.surface_distance_km(center_distance_km) * 1_000.0 / SPEED_OF_LIGHT
+    }
+
+    /// One-way light time when the receiver moves away at `radial_velocity_m_s`
+    /// (negative when approaching) while the signal is in flight: the signal has
+    /// to chase it, so the time is `d / (c - v)` rather than `d / c`.
+    pub fn one_way_light_time_moving(&self, center_distance_km: f64, radial_velocity_m_s: f64) -> f64 {
+        self.surface_distance_km(center_distance_km) * 1_000.0 / (SPEED_OF_LIGHT - radial_velocity_m_s)
+    }
+}
+
+pub fn print_body_scenario(body: &CelestialBody) {
+    println!("\n=== Earth -> {} Scenario ===", body.name);
+    println!("Assumptions: free-space propagation, no relay detours, distance set by orbital geometry.");
+
+    let distances = [body.min_dist_km, body.avg_dist_km, body.max_dist_km];
+    let one_way = distances.map(|d| body.one_way_light_time(d));
+
+    println!(
+        "Surface distance (min/avg/max): {:.1} / {:.1} / {:.1} million km",
+        body.surface_distance_km(distances[0]) / 1_000_000.0,
+        body.surface_distance_km(distances[1]) / 1_000_000.0,
+        body.surface_distance_km(distances[2]) / 1_000_000.0
+    );
+    println!(
+        "One-way light time (min/avg/max): {} / {} / {}",
+        format_duration(one_way[0]),
+        format_duration(one_way[1]),
+        format_duration(one_way[2])
+    );
+    println!(
+        "RTT (min/avg/max): {} / {} / {}",
+        format_duration(one_way[0] * 2.0),
+        format_duration(one_way[1] * 2.0),
+        format_duration(one_way[2] * 2.0)
+    );
+    println!(
+        "TCP handshake alone (1.5 RTT): {} to {} before the server sees a connection.",
+        format_duration(one_way[0] * 3.0),
+        format_duration(one_way[2] * 3.0)
+    );
+}
+
+/// How much the receiver's motion during transit stretches the average one-way
+/// time, for a receding speed of `radial_velocity_km_s`.
+pub fn print_motion_correction(body: &CelestialBody, radial_velocity_km_s: f64, context: &str) {
+    let still = body.one_way_light_time(body.avg_dist_km);
+    let moving = body.one_way_light_time_moving(body.avg_dist_km, radial_velocity_km_s * 1_000.0);
+    println!(
+        "Motion during transit ({}, receding at {:.1} km/s): {} -> {} (+{:.3} ms, {:.4}%)",
+        context,
+        radial_velocity_km_s,
+        format_duration(still),
+        format_duration(moving),
+        (moving - still) * 1_000.0,
+        (moving / still - 1.0) * 100.0
+    );
+    println!("Not modelled: gravitational (Shapiro) delay, a few hundred microseconds when the path grazes the Sun.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn venus_light_time_spans_its_distance_extremes() {
+        let minutes = |distance_km: f64| VENUS.one_way_light_time(distance_km) / 60.0;
+        // About 2 minutes at inferior conjunction and 14.5 at superior conjunction
+        assert!((2.0..2.2).contains(&minutes(VENUS.min_dist_km)), "{} min", minutes(VENUS.min_dist_km));
+        assert!((14.3..14.6).contains(&minutes(VENUS.max_dist_km)), "{} min", minutes(VENUS.max_dist_km));
+        assert!(minutes(VENUS.avg_dist_km) > minutes(VENUS.min_dist_km));
+        assert!(minutes(VENUS.avg_dist_km) < minutes(VENUS.max_dist_km));
+    }
+
+    #[test]
+    fn motion_correction_is_negligible_for_the_moon_but_not_a_fast_probe() {
+        let relative_change = |body: &CelestialBody, velocity: f64| {
+            let still = body.one_way_light_time(body.avg_dist_km);
+            (body.one_way_light_time_moving(body.avg_dist_km, velocity) - still) / still
+        };
+        // The Moon's distance changes by at most about 0.1 km/s
+        assert!(relative_change(&MOON, 100.0).abs() < 1e-4);
+        // A probe receding at 3,000 km/s
+        let probe = relative_change(&MARS, 3_000_000.0);
+        assert!(probe > 0.009, "{}", probe);
+        assert!(relative_change(&MARS, -3_000_000.0) < 0.0);
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+pub const DEFAULT_TOPOLOGY: &str = "topologies/southern_africa.graphml";
+/// Shows the menu even when stdin isn't a terminal, e.g. for piped choices.
+pub const MENU_FLAG: &str = "--menu";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scenario {
+    /// The full terrestrial demo followed by the space scenarios.
+    Terrestrial,
+    Moon,
+    /// Mars, Venus and Jupiter.
+    Planets,
+    ProtocolComparison,
+    /// A GraphML file to load and summarize.
+    Topology(String),
+    Quit,
+}
+
+pub fn print_menu() {
+    println!("=== Simulation Hypothesis ===");
+    println!("  1) Terrestrial network demo (default)");
+    println!("  2) Earth <-> Moon");
+    println!("  3) Earth <-> Mars and the other planets");
+    println!("  4) Protocol startup comparison over the Moon link");
+    println!("  5 [path]) Custom GraphML topology (default: {})", DEFAULT_TOPOLOGY);
+    println!("  q) Quit");
+}
+
+/// Reads one menu choice. Anything passed on the command line is used as the
+/// choice directly. Otherwise the menu is shown and stdin is read until a valid
+/// line arrives, but only if stdin is a terminal or `MENU_FLAG` was given, so
+/// unattended runs (CI, redirected input) go straight to the default demo. A
+/// blank line or end of input also picks the default.
+pub fn choose_scenario() -> Scenario {
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|arg| arg == MENU_FLAG);
+    if !args.is_empty() {
+        let line = args.join(" ");
+        return parse_choice(&line).unwrap_or_else(|| {
+            eprintln!("Unrecognized choice '{}', running the default demo.", line);
+            Scenario::Terrestrial
+        });
+    }
+
+    let stdin = io::stdin();
+    if flags.is_empty() && !stdin.is_terminal() {
+        return Scenario::Terrestrial;
+    }
+    print_menu();
+    loop {
+        print!("Choice [1]: ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return Scenario::Terrestrial,
+            Ok(_) => match parse_choice(&line) {
+                Some(scenario) => return scenario,
+                None => println!("Unrecognized choice '{}'.", line.trim()),
+            },
+        }
+    }
+}
+
+/// Maps one line of input to a scenario, or `None` if it isn't a menu entry.
+pub fn parse_choice(line: &str) -> Option<Scenario> {
+    let line = line.trim();
+    let (choice, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let argument = argument.trim();
+    match (choice.to_lowercase().as_str(), argument) {
+        ("" | "1" | "terrestrial", "") => Some(Scenario::Terrestrial),
+        ("2" | "moon", "") => Some(Scenario::Moon),
+        ("3" | "mars" | "planets", "") => Some(Scenario::Planets),
+        ("4" | "protocols", "") => Some(Scenario::ProtocolComparison),
+        ("5" | "topology", "") => Some(Scenario::Topology(DEFAULT_TOPOLOGY.to_string())),
+        ("5" | "topology", path) => Some(Scenario::Topology(path.to_string())),
+        ("q" | "quit" | "exit", "") => Some(Scenario::Quit),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_menu_entry_maps_to_its_scenario() {
+        let cases = [
+            ("", Scenario::Terrestrial),
+            ("1", Scenario::Terrestrial),
+            ("2", Scenario::Moon),
+            ("moon", Scenario::Moon),
+            ("3", Scenario::Planets),
+            ("4", Scenario::ProtocolComparison),
+            ("5", Scenario::Topology(DEFAULT_TOPOLOGY.to_string())),
+            ("5 net.graphml", Scenario::Topology("net.graphml".to_string())),
+            ("Q", Scenario::Quit),
+        ];
+        for (line, scenario) in cases {
+            assert_eq!(parse_choice(line), Some(scenario), "choice {:?}", line);
+        }
+        assert_eq!(parse_choice("6"), None);
+        assert_eq!(parse_choice("2 extra"), None);
+    }
+}
@@ -1,21 +1,31 @@
 use std::f64::consts::PI;
 
+use crate::celestial::MOON;
 use crate::network_core::SPEED_OF_LIGHT;
 
-const EARTH_RADIUS_KM: f64 = 6_371.0;
-const MOON_RADIUS_KM: f64 = 1_737.4;
-const EARTH_MOON_AVG_KM: f64 = 384_400.0;
-const EARTH_MOON_PERIGEE_KM: f64 = 363_300.0;
-const EARTH_MOON_APOGEE_KM: f64 = 405_500.0;
 const ANOMALISTIC_MONTH_DAYS: f64 = 27.55455;
+const ORBITAL_TABLE_DAYS: f64 = 27.0;
+const ORBITAL_TABLE_INTERVAL_DAYS: f64 = 3.0;
 
 const LIBRATION_LONGITUDE_AMPLITUDE_DEG: f64 = 7.9;
 const SIDEREAL_MONTH_DAYS: f64 = 27.321661;
+const LIBRATION_LATITUDE_AMPLITUDE_DEG: f64 = 6.7;
+const DRACONIC_MONTH_DAYS: f64 = 27.212221;
 
 const LUNAR_SITE_LONGITUDE_DEG: f64 = 95.0;
+const LUNAR_SITE_LATITUDE_DEG: f64 = 0.0;
+const LINE_OF_SIGHT_HORIZON_DAYS: usize = 28;
 
-const RELAY_EXTRA_PATH_KM: f64 = 12_000.0;
-const RELAY_ASSUMED_UPTIME: f64 = 99.8;
+const MOON_GM_KM3_S2: f64 = 4_902.8;
+const RELAY_ALTITUDE_KM: f64 = 1_000.0;
+const RELAY_CONSTELLATION_SIZE: usize = 4;
+const RELAY_SAMPLE_MINUTES: usize = 10;
+
+/// Measured coverage of a relay constellation for one lunar ground site.
+pub struct RelayCoverage {
+    pub uptime: f64,
+    pub avg_added_path_km: f64,
+}
 
 struct ProtocolProfile {
     name: &'static str,
@@ -24,7 +34,7 @@ struct ProtocolProfile {
 }
 
 fn surface_distance_km(center_distance_km: f64) -> f64 {
-    center_distance_km - EARTH_RADIUS_KM - MOON_RADIUS_KM
+    MOON.surface_distance_km(center_distance_km)
 }
 
 fn one_way_ms(surface_distance_km: f64) -> f64 {
@@ -32,8 +42,8 @@ fn one_way_ms(surface_distance_km: f64) -> f64 {
 }
 
 fn orbital_center_distance_km(day: f64) -> f64 {
-    let average = (EARTH_MOON_APOGEE_KM + EARTH_MOON_PERIGEE_KM) / 2.0;
-    let amplitude = (EARTH_MOON_APOGEE_KM - EARTH_MOON_PERIGEE_KM) / 2.0;
+    let average = (MOON.max_dist_km + MOON.min_dist_km) / 2.0;
+    let amplitude = (MOON.max_dist_km - MOON.min_dist_km) / 2.0;
     average - amplitude * ((2.0 * PI * day) / ANOMALISTIC_MONTH_DAYS).cos()
 }
 
@@ -51,9 +61,121 @@ fn sub_earth_longitude_deg(day: f64) -> f64 {
     LIBRATION_LONGITUDE_AMPLITUDE_DEG * ((2.0 * PI * day) / SIDEREAL_MONTH_DAYS).sin()
 }
 
+fn sub_earth_latitude_deg(day: f64) -> f64 {
+    LIBRATION_LATITUDE_AMPLITUDE_DEG * ((2.0 * PI * day) / DRACONIC_MONTH_DAYS).sin()
+}
+
+/// Earth is above the site's horizon when the site is within 90 degrees of the
+/// sub-Earth point on the lunar surface.
+fn earth_visible_from_site(day: f64, site_longitude_deg: f64, site_latitude_deg: f64) -> bool {
+    let site_lat = site_latitude_deg.to_radians();
+    let earth_lat = sub_earth_latitude_deg(day).to_radians();
+    let dlon = normalize_degrees(site_longitude_deg - sub_earth_longitude_deg(day)).to_radians();
+    let cos_separation = site_lat.sin() * earth_lat.sin() + site_lat.cos() * earth_lat.cos() * dlon.cos();
+    cos_separation >= 0.0
+}
+
+fn unit_vector(latitude_deg: f64, longitude_deg: f64) -> [f64; 3] {
+    let (lat, lon) = (latitude_deg.to_radians(), longitude_deg.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// Measures how often at least one relay in a polar constellation can see both
+/// the ground site and Earth, sampled every few minutes over the horizon.
+///
+/// Relays are evenly phased in a circular polar orbit whose plane passes over the
+/// site's meridian (held fixed in the Moon-fixed frame). When Earth is directly
+/// visible the site uses the direct link; otherwise the relay with the shortest
+/// detour carries the traffic, and its extra path length is averaged.
+pub fn relay_constellation_coverage(num_relays: usize, site_longitude_deg: f64, site_latitude_deg: f64) -> RelayCoverage {
+    let orbit_radius_km = MOON.radius_km + RELAY_ALTITUDE_KM;
+    let period_days = 2.0 * PI * (orbit_radius_km.powi(3) / MOON_GM_KM3_S2).sqrt() / 86_400.0;
+    let site_up = unit_vector(site_latitude_deg, site_longitude_deg);
+    let site = site_up.map(|c| c * MOON.radius_km);
+    let node = unit_vector(0.0, site_longitude_deg);
+
+    let total_samples = LINE_OF_SIGHT_HORIZON_DAYS * 24 * 60 / RELAY_SAMPLE_MINUTES;
+    let mut covered = 0usize;
+    let mut relayed = 0usize;
+    let mut added_path_sum_km = 0.0;
+
+    for sample in 0..total_samples {
+        let day = (sample * RELAY_SAMPLE_MINUTES) as f64 / (24.0 * 60.0);
+        if earth_visible_from_site(day, site_longitude_deg, site_latitude_deg) {
+            covered += 1;
+            continue;
+        }
+
+        let earth = unit_vector(sub_earth_latitude_deg(day), sub_earth_longitude_deg(day));
+        let best_detour_km = (0..num_relays)
+            .filter_map(|i| {
+                let phase = 2.0 * PI * (day / period_days + i as f64 / num_relays as f64);
+                let relay = [
+                    orbit_radius_km * phase.cos() * node[0],
+                    orbit_radius_km * phase.cos() * node[1],
+                    orbit_radius_km * phase.sin(),
+                ];
+                let to_relay = sub(relay, site);
+                let sees_site = dot(to_relay, site_up) > 0.0;
+                // Earth is effectively at infinity: the relay sees it unless the Moon's disc is in the way
+                let along = dot(relay, earth);
+                let off_axis = sub(relay, earth.map(|c| c * along));
+                let sees_earth = along >= 0.0 || dot(off_axis, off_axis).sqrt() > MOON.radius_km;
+                (sees_site && sees_earth).then(|| dot(to_relay, to_relay).sqrt() - dot(to_relay, earth))
+            })
+            .fold(None, |best: Option<f64>, detour| Some(best.map_or(detour, |b| b.min(detour))));
+
+        if let Some(detour_km) = best_detour_km {
+            covered += 1;
+            relayed += 1;
+            added_path_sum_km += detour_km;
+        }
+    }
+
+    RelayCoverage {
+        uptime: covered as f64 / total_samples as f64,
+        avg_added_path_km: if relayed > 0 { added_path_sum_km / relayed as f64 } else { 0.0 },
+    }
+}
+
+/// Fraction of the sampled horizon (hourly, 28 days) during which a lunar site
+/// at the given coordinates has direct line-of-sight to Earth.
+pub fn site_uptime_fraction(site_longitude_deg: f64, site_latitude_deg: f64) -> f64 {
+    let total_hours = LINE_OF_SIGHT_HORIZON_DAYS * 24;
+    let visible_hours = (0..total_hours)
+        .filter(|hour| earth_visible_from_site(*hour as f64 / 24.0, site_longitude_deg, site_latitude_deg))
+        .count();
+    visible_hours as f64 / total_hours as f64
+}
+
+/// Surface-to-surface Earth-Moon round-trip time as (day, RTT in ms), sampled
+/// every `interval_days` from day 0 through `days`. Day 0 is perigee. Empty
+/// unless both are finite and the interval is positive.
+pub fn orbital_rtt_series(days: f64, interval_days: f64) -> Vec<(f64, f64)> {
+    if !days.is_finite() || !interval_days.is_finite() || interval_days <= 0.0 || days < 0.0 {
+        return Vec::new();
+    }
+    // The epsilon keeps 0.3 / 0.1 = 2.9999999999999996 from losing its last sample
+    let samples = ((days / interval_days) + 1e-9).floor() as usize + 1;
+    (0..samples)
+        .map(|i| {
+            let day = i as f64 * interval_days;
+            (day, one_way_ms(surface_distance_km(orbital_center_distance_km(day))) * 2.0)
+        })
+        .collect()
+}
+
 pub fn print_top_three_extensions() {
     print_orbital_dynamics_extension();
-    print_line_of_sight_extension();
+    print_line_of_sight_extension(LUNAR_SITE_LONGITUDE_DEG, LUNAR_SITE_LATITUDE_DEG);
     print_protocol_comparison_extension();
 }
 
@@ -65,22 +187,16 @@ fn print_orbital_dynamics_extension() {
     let mut min_rtt_ms = f64::MAX;
     let mut max_rtt_ms: f64 = 0.0;
 
-    let mut day = 0.0;
-    while day <= 27.0 {
-        let center_km = orbital_center_distance_km(day);
-        let surface_km = surface_distance_km(center_km);
-        let one_way = one_way_ms(surface_km);
-        let rtt = one_way * 2.0;
+    for (day, rtt) in orbital_rtt_series(ORBITAL_TABLE_DAYS, ORBITAL_TABLE_INTERVAL_DAYS) {
+        let surface_km = surface_distance_km(orbital_center_distance_km(day));
 
         min_rtt_ms = min_rtt_ms.min(rtt);
         max_rtt_ms = max_rtt_ms.max(rtt);
 
         println!(
             "{:>3.0} | {:>21.0} | {:>11.0} | {:>8.0}",
-            day, surface_km, one_way, rtt
+            day, surface_km, rtt / 2.0, rtt
         );
-
-        day += 3.0;
     }
 
     println!(
@@ -91,32 +207,27 @@ fn print_orbital_dynamics_extension() {
     );
 }
 
-fn print_line_of_sight_extension() {
+fn print_line_of_sight_extension(site_longitude_deg: f64, site_latitude_deg: f64) {
     println!("Line-of-Sight Outages and Relay Impact");
-    println!("Lunar site longitude: {:.1} degrees (near far-side limb).", LUNAR_SITE_LONGITUDE_DEG);
-    println!("Sim horizon: 28 days sampled hourly.");
+    println!(
+        "Lunar site: longitude {:.1} degrees, latitude {:.1} degrees.",
+        site_longitude_deg, site_latitude_deg
+    );
+    println!("Sim horizon: {} days sampled hourly.", LINE_OF_SIGHT_HORIZON_DAYS);
 
-    let total_hours = 28 * 24;
+    let total_hours = LINE_OF_SIGHT_HORIZON_DAYS * 24;
     let mut visible_hours = 0usize;
     let mut direct_one_way_sum_ms = 0.0;
-    let mut relay_one_way_sum_ms = 0.0;
 
     for hour in 0..total_hours {
         let day = hour as f64 / 24.0;
         let center_km = orbital_center_distance_km(day);
         let surface_km = surface_distance_km(center_km);
         let one_way_direct_ms = one_way_ms(surface_km);
-        let earth_sub_long = sub_earth_longitude_deg(day);
-        let separation = normalize_degrees(LUNAR_SITE_LONGITUDE_DEG - earth_sub_long).abs();
-        let visible = separation <= 90.0;
-
-        if visible {
+        if earth_visible_from_site(day, site_longitude_deg, site_latitude_deg) {
             visible_hours += 1;
             direct_one_way_sum_ms += one_way_direct_ms;
         }
-
-        let relay_penalty_ms = one_way_ms(RELAY_EXTRA_PATH_KM);
-        relay_one_way_sum_ms += one_way_direct_ms + relay_penalty_ms;
     }
 
     let direct_uptime = (visible_hours as f64 / total_hours as f64) * 100.0;
@@ -125,27 +236,44 @@ fn print_line_of_sight_extension() {
     } else {
         f64::INFINITY
     };
-    let relay_avg_one_way_ms = relay_one_way_sum_ms / total_hours as f64;
+    let relay = relay_constellation_coverage(RELAY_CONSTELLATION_SIZE, site_longitude_deg, site_latitude_deg);
 
     println!(
         "Without relay: uptime {:.1}% | avg one-way when visible: {:.0} ms",
         direct_uptime, direct_avg_one_way_ms
     );
     println!(
-        "With relay: uptime {:.1}% | avg one-way: {:.0} ms",
-        RELAY_ASSUMED_UPTIME, relay_avg_one_way_ms
+        "With {} relays: uptime {:.1}% | avg one-way when relayed: {:.0} ms",
+        RELAY_CONSTELLATION_SIZE,
+        relay.uptime * 100.0,
+        one_way_ms(surface_distance_km(MOON.avg_dist_km) + relay.avg_added_path_km)
     );
     println!(
         "Tradeoff: relay adds ~{:.0} ms one-way but recovers coverage.",
-        one_way_ms(RELAY_EXTRA_PATH_KM)
+        one_way_ms(relay.avg_added_path_km)
+    );
+
+    println!("Relays | Uptime | Avg added path (km)");
+    for num_relays in 1..=6 {
+        let coverage = relay_constellation_coverage(num_relays, site_longitude_deg, site_latitude_deg);
+        println!(
+            "{:>6} | {:>5.1}% | {:>19.0}",
+            num_relays,
+            coverage.uptime * 100.0,
+            coverage.avg_added_path_km
+        );
+    }
+    println!(
+        "Reference: a near-side site at (0, 0) sees Earth {:.1}% of the time.",
+        site_uptime_fraction(0.0, 0.0) * 100.0
     );
 }
 
-fn print_protocol_comparison_extension() {
+pub fn print_protocol_comparison_extension() {
     println!("Protocol Startup Comparison");
     println!("Baseline distance: average Earth-Moon separation.");
 
-    let surface_km = surface_distance_km(EARTH_MOON_AVG_KM);
+    let surface_km = surface_distance_km(MOON.avg_dist_km);
     let one_way = one_way_ms(surface_km);
     let rtt = one_way * 2.0;
 
@@ -189,4 +317,43 @@ fn print_protocol_comparison_extension() {
         "DTN/LTP (scheduled contact) | wait + one-way delivery | {:>8.0}  (best for bulk, not chatty RPC)",
         dtn_total_ms
     );
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_side_limb_site_loses_line_of_sight() {
+        let near_side = site_uptime_fraction(0.0, 0.0);
+        let limb = site_uptime_fraction(LUNAR_SITE_LONGITUDE_DEG, LUNAR_SITE_LATITUDE_DEG);
+        assert!(near_side > 0.99, "near side {}", near_side);
+        assert!(limb < 0.9 * near_side, "limb {} vs near side {}", limb, near_side);
+    }
+
+    #[test]
+    fn relay_uptime_grows_with_constellation_size() {
+        let uptimes: Vec<f64> = (1..=6)
+            .map(|relays| relay_constellation_coverage(relays, LUNAR_SITE_LONGITUDE_DEG, LUNAR_SITE_LATITUDE_DEG).uptime)
+            .collect();
+        assert!(uptimes.windows(2).all(|pair| pair[1] >= pair[0]), "{:?}", uptimes);
+        assert!(uptimes[5] > uptimes[0]);
+    }
+
+    #[test]
+    fn rtt_series_is_shortest_at_perigee() {
+        let series = orbital_rtt_series(20.0, 0.25);
+        assert_eq!(series.len(), 81);
+        assert_eq!((series[0].0, series[80].0), (0.0, 20.0));
+        let fastest = |series: &[(f64, f64)]| series.iter().min_by(|a, b| a.1.total_cmp(&b.1)).map(|(day, _)| *day);
+        assert_eq!(fastest(&series), Some(0.0));
+        // The next perigee, one anomalistic month on, is the minimum of a longer run
+        let later: Vec<(f64, f64)> = orbital_rtt_series(40.0, 0.25).into_iter().filter(|(day, _)| *day > 1.0).collect();
+        let next_perigee = fastest(&later).unwrap();
+        assert!((next_perigee - ANOMALISTIC_MONTH_DAYS).abs() <= 0.25, "minimum at day {}", next_perigee);
+        assert_eq!(orbital_rtt_series(0.3, 0.1).len(), 4);
+        assert!(orbital_rtt_series(10.0, 0.0).is_empty());
+        assert!(orbital_rtt_series(10.0, -1.0).is_empty());
+        assert!(orbital_rtt_series(f64::INFINITY, 0.25).is_empty());
+        assert!(orbital_rtt_series(10.0, f64::NAN).is_empty());
+    }
+}
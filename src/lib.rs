@@ -0,0 +1,12 @@
+pub mod network_core;
+pub mod celestial;
+pub mod theories;
+pub mod moon_scenario;
+pub mod earth_moon_extensions;
+pub mod mars_scenario;
+pub mod rng;
+pub mod topology;
+pub mod topogen;
+pub mod cli;
+pub mod time;
+pub mod trace;
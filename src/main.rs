@@ -1,59 +1,112 @@
-mod network_core;
-mod theories;
-mod moon_scenario;
-mod earth_moon_extensions;
-
-use network_core::{NetworkSimulation, Server, Client, GeoLocation, SPEED_OF_LIGHT};
-use theories::TheoryTests;
+use simulation_hypothesis::cli::{self, Scenario};
+use simulation_hypothesis::network_core::EarthModel;
+use simulation_hypothesis::theories::{terrestrial_topology, TheoryTests};
+use simulation_hypothesis::{celestial, earth_moon_extensions, mars_scenario, moon_scenario, topogen, topology};
 
 fn main() {
-    let mut sim = NetworkSimulation::new();
-    
-    // Setup Nodes
-    sim.add_server(Server {
-        id: 1,
-        location: GeoLocation { latitude: -26.2041, longitude: 28.0473, name: "Johannesburg".to_string() },
-        processing_delay: 0.0005,
-        bandwidth: 100_000_000_000.0,
-    });
-    
-    sim.add_server(Server {
-        id: 5,
-        location: GeoLocation { latitude: 40.7128, longitude: -74.0060, name: "New York".to_string() },
-        processing_delay: 0.0006,
-        bandwidth: 200_000_000_000.0,
-    });
-
-    sim.add_client(Client {
-        id: 100,
-        location: GeoLocation { latitude: -25.7479, longitude: 28.2293, name: "Pretoria".to_string() },
-    });
-
-    // Setup Links
-    let bandwidth = 10_000_000_000.0;
-    sim.connect_nodes(100, 1, bandwidth); 
-    sim.connect_nodes(1, 5, bandwidth);   
-    sim.connect_nodes(5, 1, bandwidth);   
-    sim.connect_nodes(1, 100, bandwidth);
-
-    // Run Theories
-    TheoryTests::demonstrate_tcp_handshake(&mut sim, 100, 5);
+    match cli::choose_scenario() {
+        Scenario::Terrestrial => run_terrestrial_demo(),
+        Scenario::Moon => run_moon_scenarios(),
+        Scenario::Planets => run_planet_scenarios(),
+        Scenario::ProtocolComparison => earth_moon_extensions::print_protocol_comparison_extension(),
+        Scenario::Topology(path) => print_imported_topology(&path),
+        Scenario::Quit => {}
+    }
+}
+
+fn run_terrestrial_demo() {
+    let mut sim = terrestrial_topology();
+
+    println!("\n=== Node Registry ===");
+    let mut nodes: Vec<_> = sim.all_nodes().collect();
+    nodes.sort_by_key(|(id, _)| *id);
+    for (id, loc) in nodes {
+        println!("{:>4} | {:<12} | {:>8.4}, {:>9.4}", id, loc.name, loc.latitude, loc.longitude);
+    }
+    println!(
+        "Diameter: {:.2} ms one-way | Average path latency: {:.2} ms",
+        sim.network_diameter() * 1000.0,
+        sim.average_path_latency() * 1000.0
+    );
+    for warning in sim.check_oversubscription() {
+        println!(
+            "Oversubscribed: {} takes in {:.1} Gbps but can send only {:.1} Gbps ({:.1}:1)",
+            sim.get_node_name(warning.node),
+            warning.inbound_bps / 1_000_000_000.0,
+            warning.outbound_bps / 1_000_000_000.0,
+            warning.ratio
+        );
+    }
+
+    // Script the theories by name rather than raw ids
+    let node = |name: &str| sim.node_id_by_name(name).expect("Node not registered");
+    let (pretoria, cape_town) = (node("Pretoria"), node("Cape Town"));
+    let (johannesburg, new_york, london) = (node("Johannesburg"), node("New York"), node("London"));
+
+    // Run Theories (two clients handshaking concurrently)
+    TheoryTests::demonstrate_tcp_handshake(&mut sim, pretoria, new_york);
+    TheoryTests::demonstrate_tcp_handshake(&mut sim, cape_town, new_york);
     sim.run_simulation(1.0); 
+    for connection in &sim.connections {
+        let rtt = sim.path_rtt(connection.client, connection.server).unwrap_or(f64::NAN);
+        println!(
+            "Handshake {} -> {}: SYN at {:.4}s, established at {:.4}s | {:.2} ms = {:.2} x path RTT",
+            sim.get_node_name(connection.client),
+            sim.get_node_name(connection.server),
+            connection.syn_sent_at,
+            connection.established_at,
+            connection.setup_time * 1000.0,
+            connection.setup_time / rtt
+        );
+    }
     
     // Demonstrate Bufferbloat (Queuing)
-    TheoryTests::demonstrate_bufferbloat(&mut sim, 100, 1);
+    TheoryTests::demonstrate_bufferbloat(&mut sim, pretoria, johannesburg);
     sim.run_simulation(2.0);
-    
+
     // Demonstrate Edge Computing
-    TheoryTests::demonstrate_cdn_solution(&mut sim, 100, 5, 1);
+    TheoryTests::demonstrate_cdn_solution(&mut sim, pretoria, new_york, johannesburg);
     sim.run_simulation(3.0);
 
-    sim.analyze_results();
+    // Show why a default TCP window can't fill an intercontinental pipe
+    TheoryTests::demonstrate_bandwidth_delay_product(&sim, johannesburg, new_york);
+
+    // Demonstrate incast collapse on a rack of equidistant servers
+    let mut rack = topogen::star(18);
+    rack.verbose = false;
+    TheoryTests::demonstrate_incast(&mut rack, 2, &(3..=18).collect::<Vec<_>>());
+
+    // Demonstrate the cost of serial request/response round trips
+    TheoryTests::demonstrate_chatty_vs_batched(&mut sim, pretoria, new_york);
+
+    // Demonstrate the analytic TCP throughput ceiling under loss
+    TheoryTests::demonstrate_mathis_throughput(&mut sim, cape_town, johannesburg);
+
+    sim.analyze_results(0.0);
+
+    println!("\n=== SLA Check ===");
+    for (src, dst) in [(pretoria, johannesburg), (johannesburg, new_york), (johannesburg, london)] {
+        let sla = sim.check_sla(src, dst, 200.0);
+        println!(
+            "{} -> {} | {} | p99 {:.2} ms vs budget {:.0} ms over {} packets | margin {:+.2} ms",
+            sim.get_node_name(src),
+            sim.get_node_name(dst),
+            if sla.passed { "PASS" } else { "FAIL" },
+            sla.measured_p99_ms,
+            sla.budget_ms,
+            sla.samples,
+            sla.margin_ms
+        );
+    }
 
     println!("\n=== Final Physics Takeaway ===");
-    let dist = sim.calculate_distance(100, 5);
+    let dist = sim.calculate_distance(pretoria, new_york);
     println!("Physical Distance PTA -> NYC: {:.0} km", dist / 1000.0);
-    println!("Min Theoretical RTT (Vacuum): {:.2} ms", (dist * 2.0 / SPEED_OF_LIGHT) * 1000.0);
+    sim.physics.earth_model = EarthModel::Wgs84;
+    let ellipsoidal = sim.calculate_distance(pretoria, new_york);
+    sim.physics.earth_model = EarthModel::Sphere;
+    println!("On the WGS84 ellipsoid: {:.0} km ({:+.2}% vs. the spherical estimate)", ellipsoidal / 1000.0, (ellipsoidal / dist - 1.0) * 100.0);
+    println!("Min Theoretical RTT (Vacuum, along route): {:.2} ms", sim.theoretical_min_latency(pretoria, new_york) * 2.0 * 1000.0);
     println!("Actual Simulated RTT (Fiber + Winding + Handshake): Shows why you see 350ms+ in the real world.");
     
     println!("\n=== The Edge Computing Conclusion ===");
@@ -62,6 +115,33 @@ fn main() {
     println!("Result: Latency dropped from ~160ms (NYC) to ~0.7ms (JHB Edge).");
     println!("Conclusion: Don't just optimize code; optimize the geography of your data.");
 
+    print_imported_topology(cli::DEFAULT_TOPOLOGY);
+    run_moon_scenarios();
+    run_planet_scenarios();
+}
+
+fn print_imported_topology(path: &str) {
+    println!("\n=== Imported Topology (GraphML) ===");
+    match topology::load_graphml(path) {
+        Ok(imported) => {
+            let mut names: Vec<String> = imported.all_nodes().map(|(_, loc)| loc.name.clone()).collect();
+            names.sort();
+            println!("{} nodes ({}) | {} directional links", names.len(), names.join(", "), imported.links.len());
+            println!("Diameter: {:.2} ms one-way", imported.network_diameter() * 1000.0);
+        }
+        Err(e) => println!("Skipped: {}", e),
+    }
+}
+
+fn run_moon_scenarios() {
     moon_scenario::print_earth_moon_scenario();
+    celestial::print_motion_correction(&celestial::MOON, 1.022, "Moon's full orbital speed as an upper bound");
     earth_moon_extensions::print_top_three_extensions();
 }
+
+fn run_planet_scenarios() {
+    mars_scenario::print_earth_mars_scenario();
+    celestial::print_motion_correction(&celestial::MARS, 17.0, "a probe at Voyager 1's speed");
+    celestial::print_body_scenario(&celestial::VENUS);
+    celestial::print_body_scenario(&celestial::JUPITER);
+}
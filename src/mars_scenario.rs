@@ -0,0 +1,61 @@
+use crate::celestial::MARS;
+
+fn one_way_minutes(center_distance_km: f64) -> f64 {
+    MARS.one_way_light_time(center_distance_km) / 60.0
+}
+
+pub fn print_earth_mars_scenario() {
+    println!("\n=== Earth -> Mars Deep-Space Scenario ===");
+    println!("Assumptions: free-space propagation, no relay detours, distance set by orbital geometry.");
+    println!("Distance varies from closest approach (opposition) to farthest (solar conjunction).");
+
+    let min_km = MARS.surface_distance_km(MARS.min_dist_km);
+    let avg_km = MARS.surface_distance_km(MARS.avg_dist_km);
+    let max_km = MARS.surface_distance_km(MARS.max_dist_km);
+
+    let min_ow_min = one_way_minutes(MARS.min_dist_km);
+    let avg_ow_min = one_way_minutes(MARS.avg_dist_km);
+    let max_ow_min = one_way_minutes(MARS.max_dist_km);
+
+    println!(
+        "Surface distance (min/avg/max): {:.1} / {:.1} / {:.1} million km",
+        min_km / 1_000_000.0,
+        avg_km / 1_000_000.0,
+        max_km / 1_000_000.0
+    );
+    println!(
+        "One-way light time (min/avg/max): {:.1} / {:.1} / {:.1} min",
+        min_ow_min, avg_ow_min, max_ow_min
+    );
+    println!(
+        "RTT (min/avg/max): {:.1} / {:.1} / {:.1} min",
+        min_ow_min * 2.0,
+        avg_ow_min * 2.0,
+        max_ow_min * 2.0
+    );
+    println!(
+        "TCP handshake alone (1.5 RTT): {:.0}-{:.0} min before the server sees a connection.",
+        min_ow_min * 3.0,
+        max_ow_min * 3.0
+    );
+
+    println!("\n--- Why Interactive Protocols Are Hopeless ---");
+    println!("TCP retransmission timers, TLS handshakes and HTTP request/response all assume RTTs in milliseconds.");
+    println!("At Mars distances a single RTT is longer than most connection timeouts, and near conjunction");
+    println!("the Sun blocks the link entirely for roughly two weeks.");
+    println!("DTN (Bundle Protocol + LTP) is mandatory: store bundles at each hop, forward on scheduled contacts,");
+    println!("and never wait on an end-to-end acknowledgement before sending more data.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_way_light_time_spans_published_range() {
+        // NASA quotes roughly 3 minutes at opposition and 22 minutes at conjunction
+        let (closest, farthest) = (one_way_minutes(MARS.min_dist_km), one_way_minutes(MARS.max_dist_km));
+        assert!((2.9..3.2).contains(&closest), "{} min", closest);
+        assert!((21.5..22.8).contains(&farthest), "{} min", farthest);
+    }
+}
@@ -1,18 +1,7 @@
-use crate::network_core::SPEED_OF_LIGHT;
+use crate::celestial::MOON;
 
-// Mean radii and distances based on NASA fact sheets and NASA Science references.
-const EARTH_RADIUS_KM: f64 = 6_371.0;
-const MOON_RADIUS_KM: f64 = 1_737.4;
-const EARTH_MOON_AVG_KM: f64 = 384_400.0;
-const EARTH_MOON_PERIGEE_KM: f64 = 363_300.0;
-const EARTH_MOON_APOGEE_KM: f64 = 405_500.0;
-
-fn surface_distance_km(center_distance_km: f64) -> f64 {
-    center_distance_km - EARTH_RADIUS_KM - MOON_RADIUS_KM
-}
-
-fn one_way_ms(surface_distance_km: f64) -> f64 {
-    (surface_distance_km * 1_000.0 / SPEED_OF_LIGHT) * 1_000.0
+fn one_way_ms(center_distance_km: f64) -> f64 {
+    MOON.one_way_light_time(center_distance_km) * 1_000.0
 }
 
 pub fn print_earth_moon_scenario() {
@@ -22,13 +11,13 @@ pub fn print_earth_moon_scenario() {
     println!("Assumptions: near-side line-of-sight, free-space propagation, no routing detours.");
     println!("Distance varies with lunar perigee/apogee.");
 
-    let min_km = surface_distance_km(EARTH_MOON_PERIGEE_KM);
-    let avg_km = surface_distance_km(EARTH_MOON_AVG_KM);
-    let max_km = surface_distance_km(EARTH_MOON_APOGEE_KM);
+    let min_km = MOON.surface_distance_km(MOON.min_dist_km);
+    let avg_km = MOON.surface_distance_km(MOON.avg_dist_km);
+    let max_km = MOON.surface_distance_km(MOON.max_dist_km);
 
-    let min_ow_ms = one_way_ms(min_km);
-    let avg_ow_ms = one_way_ms(avg_km);
-    let max_ow_ms = one_way_ms(max_km);
+    let min_ow_ms = one_way_ms(MOON.min_dist_km);
+    let avg_ow_ms = one_way_ms(MOON.avg_dist_km);
+    let max_ow_ms = one_way_ms(MOON.max_dist_km);
 
     let min_rtt_ms = min_ow_ms * 2.0;
     let avg_rtt_ms = avg_ow_ms * 2.0;
@@ -1,10 +1,38 @@
-use std::collections::{HashMap, BinaryHeap, VecDeque, HashSet};
+use std::collections::{HashMap, BinaryHeap, VecDeque, HashSet, BTreeMap, BTreeSet};
 use std::cmp::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::rng::SimRng;
+use crate::time::{format_duration, ms};
+use crate::trace::TraceRecord;
 
 pub const SPEED_OF_LIGHT: f64 = 299_792_458.0;
 pub const FIBER_REFRACTIVE_INDEX: f64 = 1.47;
 pub const SPEED_IN_FIBER: f64 = SPEED_OF_LIGHT / FIBER_REFRACTIVE_INDEX;
 pub const PATH_INEFFICIENCY_FACTOR: f64 = 1.3;
+/// Mean Earth radius in meters, for the spherical (haversine) model.
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+pub const DEFAULT_MTU: usize = 1500;
+pub const DEFAULT_SEED: u64 = 42;
+/// Energy to move one bit across a link, including the transceivers at each end.
+pub const DEFAULT_LINK_ENERGY_PER_BIT: f64 = 1e-9;
+pub const PRIORITY_LEVELS: usize = 3;
+/// Inbound-to-outbound capacity ratio above which `check_oversubscription` warns.
+pub const OVERSUBSCRIPTION_WARN_RATIO: f64 = 3.0;
+/// Largest TCP payload on a 1500-byte MTU path.
+pub const TCP_MSS: usize = 1460;
+/// The usual delayed-ACK timeout (Linux's minimum; Windows defaults to 200 ms).
+pub const DELAYED_ACK_TIMEOUT: f64 = 0.040;
+/// Seconds of traffic offered at each rate by `load_sweep`.
+pub const LOAD_SWEEP_DURATION: f64 = 0.5;
+/// Size of the reference a deduplicating link sends in place of a payload it has already carried.
+pub const DEDUP_REFERENCE_BYTES: usize = 64;
+/// Mixed into the seed for the traffic generators' random stream, kept apart
+/// from the network's own draws (loss, jitter, ...) so that adding generated
+/// traffic to a scenario doesn't shift those draws.
+const TRAFFIC_STREAM: u64 = 0xD1B5_4A32_D192_ED03;
 
 #[derive(Debug, Clone)]
 pub struct GeoLocation {
@@ -15,7 +43,28 @@ pub struct GeoLocation {
 
 impl GeoLocation {
     pub fn distance_to(&self, other: &GeoLocation) -> f64 {
-        const EARTH_RADIUS: f64 = 6_371_000.0;
+        self.haversine_distance(other, EARTH_RADIUS)
+    }
+
+    /// Continent from coarse latitude/longitude boxes. Good enough to label
+    /// nodes for aggregation; not a substitute for real borders.
+    pub fn continent(&self) -> &'static str {
+        let (lat, lon) = (self.latitude, self.longitude);
+        if lat < -60.0 {
+            "Antarctica"
+        } else if (-170.0..-30.0).contains(&lon) {
+            if lat > 12.0 { "North America" } else { "South America" }
+        } else if (-30.0..60.0).contains(&lon) {
+            if lat >= 36.0 { "Europe" } else if lon > 34.0 && lat > 12.0 { "Asia" } else { "Africa" }
+        } else if lat < -10.0 && !(-170.0..110.0).contains(&lon) {
+            "Oceania"
+        } else {
+            "Asia"
+        }
+    }
+
+    /// Great-circle distance on a sphere of `radius` meters.
+    pub fn haversine_distance(&self, other: &GeoLocation, radius: f64) -> f64 {
         let lat1 = self.latitude.to_radians();
         let lat2 = other.latitude.to_radians();
         let dlat = (other.latitude - self.latitude).to_radians();
@@ -23,25 +72,180 @@ impl GeoLocation {
         let a = (dlat / 2.0).sin().powi(2) 
             + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
         let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-        EARTH_RADIUS * c
+        radius * c
+    }
+
+    /// Point a fraction `t` of the way along the great circle to `other`
+    /// (spherical linear interpolation). Undefined for antipodal points, where
+    /// every great circle through both is equally short.
+    pub fn interpolate(&self, other: &GeoLocation, t: f64) -> GeoLocation {
+        let unit_vector = |loc: &GeoLocation| {
+            let (lat, lon) = (loc.latitude.to_radians(), loc.longitude.to_radians());
+            [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+        };
+        let (a, b) = (unit_vector(self), unit_vector(other));
+        let angle = self.haversine_distance(other, 1.0);
+        let (wa, wb) = if angle < 1e-12 {
+            (1.0 - t, t)
+        } else {
+            (((1.0 - t) * angle).sin() / angle.sin(), (t * angle).sin() / angle.sin())
+        };
+        let [x, y, z] = [0, 1, 2].map(|i| wa * a[i] + wb * b[i]);
+        GeoLocation {
+            latitude: z.atan2(x.hypot(y)).to_degrees(),
+            longitude: y.atan2(x).to_degrees(),
+            name: format!("{} -> {} ({:.0}%)", self.name, other.name, t * 100.0),
+        }
+    }
+
+    /// Geodesic distance on the WGS84 ellipsoid (Vincenty's inverse formula),
+    /// accurate to well under a millimetre where the sphere is off by up to 0.5%.
+    /// Near-antipodal points can fail to converge; those fall back to haversine.
+    pub fn vincenty_distance_to(&self, other: &GeoLocation) -> f64 {
+        const A: f64 = 6_378_137.0;
+        const F: f64 = 1.0 / 298.257_223_563;
+        const B: f64 = A * (1.0 - F);
+        const MAX_ITERATIONS: usize = 200;
+
+        let l = (other.longitude - self.longitude).to_radians();
+        let u1 = ((1.0 - F) * self.latitude.to_radians().tan()).atan();
+        let u2 = ((1.0 - F) * other.latitude.to_radians().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let (sin_u2, cos_u2) = u2.sin_cos();
+
+        let mut lambda = l;
+        for _ in 0..MAX_ITERATIONS {
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+                + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2)).sqrt();
+            if sin_sigma == 0.0 {
+                return 0.0; // coincident points
+            }
+            let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+            let sigma = sin_sigma.atan2(cos_sigma);
+            let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+            let cos_sq_alpha = 1.0 - sin_alpha * sin_alpha;
+            // Both points on the equator: cos²α is zero and the term vanishes
+            let cos_2sigma_m = if cos_sq_alpha != 0.0 { cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha } else { 0.0 };
+            let c = F / 16.0 * cos_sq_alpha * (4.0 + F * (4.0 - 3.0 * cos_sq_alpha));
+            let previous = lambda;
+            lambda = l + (1.0 - c) * F * sin_alpha
+                * (sigma + c * sin_sigma * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+            if (lambda - previous).abs() < 1e-12 {
+                let u_sq = cos_sq_alpha * (A * A - B * B) / (B * B);
+                let big_a = 1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+                let big_b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+                let delta_sigma = big_b * sin_sigma * (cos_2sigma_m + big_b / 4.0
+                    * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                        - big_b / 6.0 * cos_2sigma_m * (-3.0 + 4.0 * sin_sigma.powi(2)) * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+                return B * big_a * (sigma - delta_sigma);
+            }
+        }
+        self.distance_to(other)
+    }
+
+    pub fn distance_with(&self, other: &GeoLocation, physics: &PhysicsConfig) -> f64 {
+        match physics.earth_model {
+            EarthModel::Sphere => self.haversine_distance(other, physics.earth_radius),
+            EarthModel::Wgs84 => self.vincenty_distance_to(other),
+        }
+    }
+}
+
+/// Shape of the Earth used for great-circle distances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EarthModel {
+    /// Haversine on a 6371 km sphere. Cheap, and what the original figures use.
+    Sphere,
+    /// Vincenty geodesics on the WGS84 ellipsoid.
+    Wgs84,
+}
+
+/// Physical constants behind every distance and latency, overridable for
+/// sensitivity analysis via `NetworkSimulation::with_config`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsConfig {
+    /// Sphere radius in meters; ignored under `EarthModel::Wgs84`.
+    pub earth_radius: f64,
+    pub earth_model: EarthModel,
+    /// Signal speed in the cable, m/s.
+    pub speed_in_fiber: f64,
+    /// Cable length over great-circle distance for links without waypoints.
+    pub path_inefficiency_factor: f64,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            earth_radius: EARTH_RADIUS,
+            earth_model: EarthModel::Sphere,
+            speed_in_fiber: SPEED_IN_FIBER,
+            path_inefficiency_factor: PATH_INEFFICIENCY_FACTOR,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerRole {
+    /// Holds every piece of content and always serves it directly.
+    Origin,
+    /// Serves from its cache, fetching from `origin` and caching on a miss.
+    Edge,
+    /// Proxies every request to `origin` and passes the response back, caching nothing.
+    Relay,
+}
+
 #[derive(Debug, Clone)]
 pub struct Server {
     pub id: usize,
     pub location: GeoLocation,
     pub processing_delay: f64,
+    /// Per-packet service time drawn at each hop instead of the flat
+    /// `processing_delay`, when set.
+    pub service_time_distribution: Option<ServiceTimeDistribution>,
     pub bandwidth: f64,
+    /// How the server answers `CdnRequest`s.
+    pub role: ServerRole,
+    /// Content ids held locally. Only meaningful for edge servers.
+    pub cache: HashSet<u64>,
+    /// Upstream that edges and relays fetch from; ignored by origins.
+    pub origin: Option<usize>,
+    /// Autonomous system the server belongs to. Clients inherit their access server's AS.
+    pub as_number: u32,
+    /// Constant draw while the simulation runs, busy or idle.
+    pub power_watts: f64,
+    /// Label for aggregating results, e.g. a continent (see `GeoLocation::continent`).
+    pub region: String,
+}
+
+/// How long a server takes to process each packet it forwards.
+#[derive(Debug, Clone, Copy)]
+pub enum ServiceTimeDistribution {
+    Fixed(f64),
+    Exponential(f64),
+    /// Truncated at zero.
+    Normal { mean: f64, std_dev: f64 },
+}
+
+impl ServiceTimeDistribution {
+    pub fn sample(&self, rng: &mut SimRng) -> f64 {
+        match *self {
+            ServiceTimeDistribution::Fixed(delay) => delay,
+            ServiceTimeDistribution::Exponential(mean) => rng.exponential(mean),
+            ServiceTimeDistribution::Normal { mean, std_dev } => rng.normal(mean, std_dev).max(0.0),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Client {
     pub id: usize,
     pub location: GeoLocation,
+    pub region: String,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PacketType {
     Standard,
     TcpSyn,
@@ -49,6 +253,462 @@ pub enum PacketType {
     TcpAck,
     CdnRequest,
     CdnResponse,
+    /// Name lookup; `content_id` carries the id of the server being resolved.
+    DnsQuery,
+    DnsResponse,
+}
+
+impl PacketType {
+    /// Scheduling class: connection control jumps the line, bulk data goes last.
+    pub fn priority(&self) -> u8 {
+        match self {
+            PacketType::TcpSyn | PacketType::TcpSynAck | PacketType::TcpAck => 2,
+            PacketType::CdnRequest | PacketType::CdnResponse | PacketType::DnsQuery | PacketType::DnsResponse => 1,
+            PacketType::Standard => 0,
+        }
+    }
+}
+
+/// How traffic generators pick packet sizes.
+#[derive(Debug, Clone)]
+pub enum PacketSizeDistribution {
+    Fixed(usize),
+    /// Mix of small (ACK-like) and large (data) packets; `large_fraction` of them are large.
+    Bimodal { small: usize, large: usize, large_fraction: f64 },
+    Uniform { min: usize, max: usize },
+    /// Exponentially distributed sizes with this mean, so a single link serves
+    /// them in exponential time (the "M" service of M/M/1).
+    Exponential(usize),
+}
+
+impl PacketSizeDistribution {
+    pub fn sample(&self, rng: &mut SimRng) -> usize {
+        match *self {
+            PacketSizeDistribution::Fixed(size) => size,
+            PacketSizeDistribution::Bimodal { small, large, large_fraction } => {
+                if rng.next_f64() < large_fraction { large } else { small }
+            }
+            PacketSizeDistribution::Uniform { min, max } => rng.range_usize(min, max),
+            PacketSizeDistribution::Exponential(mean) => (rng.exponential(mean as f64).round() as usize).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DropReason {
+    NoRoute,
+    /// Lost in transit on a link with a nonzero `loss_rate`.
+    LinkLoss,
+    /// An explicit route named two consecutive nodes with no link between them.
+    InvalidSourceRoute,
+    /// Tail drop: the link's queue was already at `queue_capacity_bytes`.
+    QueueFull,
+    /// Dropped early by Random Early Detection before the queue filled.
+    RedEarly,
+    /// In flight on, or routed onto, a link that failed abruptly.
+    LinkDown,
+    /// Refused because the event queue was at `max_events`.
+    AdmissionControl,
+    /// Headed for, or waiting at, a node that was removed from the topology.
+    NodeRemoved,
+    /// Would have crossed more than `hop_limit` links, like an IP TTL running out.
+    HopLimitExceeded,
+}
+
+/// Random Early Detection thresholds. Below `min_bytes` of backlog nothing is
+/// dropped; between `min_bytes` and `max_bytes` the drop probability rises
+/// linearly to `max_drop_prob`; at or above `max_bytes` every arrival is dropped.
+/// Uses the instantaneous backlog rather than RED's usual moving average.
+#[derive(Debug, Clone, Copy)]
+pub struct RedConfig {
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    pub max_drop_prob: f64,
+}
+
+impl RedConfig {
+    pub fn drop_probability(&self, backlog_bytes: usize) -> f64 {
+        if backlog_bytes < self.min_bytes {
+            0.0
+        } else if backlog_bytes >= self.max_bytes {
+            1.0
+        } else {
+            let span = (self.max_bytes - self.min_bytes) as f64;
+            self.max_drop_prob * (backlog_bytes - self.min_bytes) as f64 / span
+        }
+    }
+}
+
+/// Reliable-delivery settings: the sender resends a packet if it hasn't been
+/// delivered within `rto_rtt_multiple` path RTTs (doubling after each retry),
+/// giving up after `max_retries`.
+#[derive(Debug, Clone, Copy)]
+pub struct RetransmitPolicy {
+    pub rto_rtt_multiple: f64,
+    pub max_retries: u32,
+}
+
+/// Per-flow TCP behaviours for streams written with `send_segment`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpOptions {
+    /// Nagle's algorithm: while a segment is unacknowledged, writes smaller
+    /// than `TCP_MSS` are coalesced and held until the ACK arrives.
+    pub nagle: bool,
+    /// Delayed ACK: every second segment is acknowledged at once, a lone one
+    /// only after this timeout unless reply data can carry the ACK sooner.
+    pub delayed_ack: Option<f64>,
+}
+
+/// Sender and receiver state of one (sender, receiver) stream.
+#[derive(Debug, Clone, Copy, Default)]
+struct TcpStream {
+    unacked_segments: usize,
+    /// Bytes Nagle is holding back.
+    nagle_buffer: usize,
+    received_since_ack: usize,
+    ack_deadline: Option<f64>,
+}
+
+/// Headline numbers for a run, so callers don't have to scrape stdout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub delivered: usize,
+    pub dropped: usize,
+    pub avg_latency: f64,
+    pub max_latency: f64,
+    pub p99_latency: f64,
+    pub duration: f64,
+}
+
+impl RunSummary {
+    /// Share of packets dropped, out of all delivered or dropped.
+    pub fn drop_rate(&self) -> f64 {
+        let total = self.delivered + self.dropped;
+        if total == 0 { 0.0 } else { self.dropped as f64 / total as f64 }
+    }
+}
+
+/// Point-in-time counters from `NetworkSimulation::metrics_snapshot`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub current_time: f64,
+    pub nodes: usize,
+    pub links: usize,
+    pub events_processed: usize,
+    pub queued_events: usize,
+    /// Packets (or fragments) handed to a link that haven't reached the next hop.
+    pub in_flight: usize,
+    pub delivered: usize,
+    pub dropped: usize,
+}
+
+/// Delivered count and latency for one `PacketType`, from `NetworkSimulation::latency_by_type`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypeLatency {
+    pub count: usize,
+    pub avg_latency: f64,
+    pub p95_latency: f64,
+}
+
+/// One fully delivered flow, from `NetworkSimulation::flow_completion_times`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlowCompletion {
+    pub flow_id: u64,
+    pub packets: usize,
+    pub bytes: usize,
+    pub started_at: f64,
+    /// Last delivery minus first creation.
+    pub fct: f64,
+}
+
+/// Flow completion times for one size class, from `NetworkSimulation::fct_distribution`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FctStats {
+    pub flows: usize,
+    pub avg_fct: f64,
+    pub p99_fct: f64,
+}
+
+/// A node that can receive much faster than it can send on, from
+/// `NetworkSimulation::check_oversubscription`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OversubWarning {
+    pub node: usize,
+    pub inbound_bps: f64,
+    pub outbound_bps: f64,
+    /// inbound / outbound
+    pub ratio: f64,
+}
+
+/// A TCP connection whose three-way handshake completed, recorded when the
+/// server receives the final ACK.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectionEstablished {
+    pub client: usize,
+    pub server: usize,
+    pub syn_sent_at: f64,
+    pub established_at: f64,
+    /// SYN sent to ACK received: about 1.5 round trips.
+    pub setup_time: f64,
+    /// Query to answer, when the connection was opened with `resolve_then_connect`.
+    pub dns_time: Option<f64>,
+}
+
+impl ConnectionEstablished {
+    /// Resolution plus handshake.
+    pub fn total_time(&self) -> f64 {
+        self.dns_time.unwrap_or(0.0) + self.setup_time
+    }
+}
+
+/// Traffic over a group of links, from `NetworkSimulation::stats_by_tag`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkStats {
+    pub links: usize,
+    pub bytes_carried: usize,
+    /// Mean fraction of the run the links spent transmitting.
+    pub avg_utilization: f64,
+    pub max_utilization: f64,
+}
+
+/// Outcome of `NetworkSimulation::check_sla` for one flow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlaResult {
+    pub passed: bool,
+    /// Infinite when nothing was delivered.
+    pub measured_p99_ms: f64,
+    pub budget_ms: f64,
+    /// Budget minus measured; negative when the SLA is missed.
+    pub margin_ms: f64,
+    pub samples: usize,
+}
+
+/// Where one hop's share of a traced packet's latency went, in seconds.
+#[derive(Debug, Clone)]
+pub struct HopTrace {
+    pub from: usize,
+    pub to: usize,
+    /// Time spent at `from` before forwarding (zero at the source).
+    pub processing: f64,
+    pub queuing: f64,
+    pub transmission: f64,
+    pub propagation: f64,
+}
+
+impl HopTrace {
+    pub fn total(&self) -> f64 {
+        self.processing + self.queuing + self.transmission + self.propagation
+    }
+}
+
+/// Hop-by-hop latency breakdown from `NetworkSimulation::trace_packet`. The hop
+/// totals add up to `total_latency`.
+#[derive(Debug, Clone)]
+pub struct PacketTrace {
+    pub hops: Vec<HopTrace>,
+    pub total_latency: f64,
+}
+
+/// When each link can carry bundles, for `NetworkSimulation::send_bundle`.
+/// Links without any window are treated as permanently in contact.
+#[derive(Debug, Clone, Default)]
+pub struct ContactSchedule {
+    pub windows: HashMap<(usize, usize), Vec<(f64, f64)>>,
+}
+
+impl ContactSchedule {
+    pub fn add_window(&mut self, from: usize, to: usize, start: f64, end: f64) {
+        let windows = self.windows.entry((from, to)).or_default();
+        windows.push((start, end));
+        windows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    /// Opens `from -> to` for `open_for` seconds every `period`, from `first_open` until `until`.
+    pub fn add_periodic(&mut self, from: usize, to: usize, first_open: f64, open_for: f64, period: f64, until: f64) {
+        let mut start = first_open;
+        while start < until {
+            self.add_window(from, to, start, start + open_for);
+            start += period;
+        }
+    }
+
+    /// Earliest time at or after `ready` that a transmission lasting `duration`
+    /// fits entirely inside a window, if any.
+    fn next_departure(&self, from: usize, to: usize, ready: f64, duration: f64) -> Option<f64> {
+        let Some(windows) = self.windows.get(&(from, to)) else { return Some(ready) };
+        windows.iter()
+            .map(|(start, end)| (start.max(ready), *end))
+            .find(|(depart, end)| depart + duration <= *end)
+            .map(|(depart, _)| depart)
+    }
+}
+
+/// Result of `NetworkSimulation::compare_chatty_vs_batched`. Times run from
+/// the first request to the last response; infinite if some went unanswered.
+#[derive(Debug, Clone, Copy)]
+pub struct ChattyBatchedReport {
+    pub requests: usize,
+    /// Each request sent only once the previous response arrived.
+    pub chatty_time: f64,
+    /// Every request sent at once.
+    pub batched_time: f64,
+}
+
+/// Result of `NetworkSimulation::send_bundle`.
+#[derive(Debug, Clone)]
+pub struct BundleDelivery {
+    pub path: Vec<usize>,
+    /// Seconds the bundle sat in custody at each node waiting for a contact.
+    pub custody: Vec<(usize, f64)>,
+    pub delivered_at: f64,
+}
+
+impl BundleDelivery {
+    pub fn total_custody_time(&self) -> f64 {
+        self.custody.iter().map(|(_, held)| held).sum()
+    }
+}
+
+/// A self-contained simulation for `run_sweep`: `setup` builds the topology and
+/// queues traffic on a fresh, quiet simulation seeded with `seed`, which then
+/// runs until `duration`.
+#[derive(Clone)]
+pub struct SimConfig {
+    pub seed: u64,
+    pub duration: f64,
+    pub setup: Arc<dyn Fn(&mut NetworkSimulation) + Send + Sync>,
+}
+
+impl SimConfig {
+    pub fn run(&self) -> RunSummary {
+        let mut sim = NetworkSimulation::new();
+        sim.verbose = false;
+        sim.set_seed(self.seed);
+        (self.setup)(&mut sim);
+        sim.run_simulation(self.duration)
+    }
+}
+
+/// Runs independent simulations on a pool of std threads (one per available
+/// core) and returns their summaries in the same order as `configs`.
+pub fn run_sweep(configs: Vec<SimConfig>) -> Vec<RunSummary> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(configs.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; configs.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+                let Some(config) = configs.get(index) else { break };
+                let summary = config.run();
+                results.lock().expect("sweep worker panicked")[index] = Some(summary);
+            });
+        }
+    });
+
+    results.into_inner()
+        .expect("sweep worker panicked")
+        .into_iter()
+        .map(|summary| summary.expect("every config is run exactly once"))
+        .collect()
+}
+
+/// Sample mean of a metric with its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl ConfidenceInterval {
+    /// Student's t interval for the mean of `samples`. Unbounded with fewer
+    /// than two samples, and centred on 0 with none.
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n.max(1.0);
+        if samples.len() < 2 {
+            return ConfidenceInterval { mean, low: f64::NEG_INFINITY, high: f64::INFINITY };
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let half_width = t_critical_95(samples.len().saturating_sub(1)) * (variance / n).sqrt();
+        ConfidenceInterval { mean, low: mean - half_width, high: mean + half_width }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        (self.low..=self.high).contains(&value)
+    }
+}
+
+/// Key metrics over independent runs of one `SimConfig`, from `run_replications`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationStats {
+    pub replications: usize,
+    pub delivered: ConfidenceInterval,
+    pub drop_rate: ConfidenceInterval,
+    pub avg_latency: ConfidenceInterval,
+    pub p99_latency: ConfidenceInterval,
+}
+
+/// Runs `config` `n` times on seeds `config.seed`, `config.seed + 1`, ... (in
+/// parallel, via `run_sweep`) and summarizes the spread across runs.
+pub fn run_replications(config: &SimConfig, n: usize) -> ReplicationStats {
+    let configs = (0..n as u64)
+        .map(|i| SimConfig { seed: config.seed.wrapping_add(i), ..config.clone() })
+        .collect();
+    let summaries = run_sweep(configs);
+    let interval = |metric: fn(&RunSummary) -> f64| {
+        ConfidenceInterval::from_samples(&summaries.iter().map(metric).collect::<Vec<_>>())
+    };
+    ReplicationStats {
+        replications: n,
+        delivered: interval(|s| s.delivered as f64),
+        drop_rate: interval(RunSummary::drop_rate),
+        avg_latency: interval(|s| s.avg_latency),
+        p99_latency: interval(|s| s.p99_latency),
+    }
+}
+
+/// A (from, to, size_bytes, type, offset) send, as taken by `send_batch`.
+pub type SendSpec = (usize, usize, usize, PacketType, f64);
+
+/// Results of running one workload on two topologies. Deltas are candidate
+/// minus baseline, so a negative latency delta means the candidate is faster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    pub baseline: RunSummary,
+    pub candidate: RunSummary,
+    pub avg_latency_delta: f64,
+    pub p99_latency_delta: f64,
+    pub drop_rate_delta: f64,
+}
+
+/// Sends `workload` through both simulations (offsets from each one's current
+/// time) and runs them until their queues drain. Only packets created from
+/// then on are counted.
+pub fn compare(baseline: &mut NetworkSimulation, candidate: &mut NetworkSimulation, workload: &[SendSpec]) -> ComparisonReport {
+    let run = |sim: &mut NetworkSimulation| {
+        let start = sim.current_time;
+        sim.send_batch(workload);
+        sim.run_simulation(f64::INFINITY);
+        sim.summarize(start)
+    };
+    let (baseline, candidate) = (run(baseline), run(candidate));
+    ComparisonReport {
+        avg_latency_delta: candidate.avg_latency - baseline.avg_latency,
+        p99_latency_delta: candidate.p99_latency - baseline.p99_latency,
+        drop_rate_delta: candidate.drop_rate() - baseline.drop_rate(),
+        baseline,
+        candidate,
+    }
+}
+
+/// Mean time in an M/M/1 system (waiting plus service), `1 / (mu - lambda)`,
+/// for Poisson arrivals at `arrival_rate` and exponential service at
+/// `service_rate` per second. Infinite once the queue is unstable.
+pub fn mm1_mean_delay(arrival_rate: f64, service_rate: f64) -> f64 {
+    if arrival_rate >= service_rate { f64::INFINITY } else { 1.0 / (service_rate - arrival_rate) }
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +719,188 @@ pub struct DataPacket {
     pub size_bytes: usize,
     pub created_at: f64,
     pub packet_type: PacketType,
+    pub content_id: u64,
+    /// Fingerprint of the bytes carried, from the content id and size; 0 when
+    /// the packet carries no identified content.
+    pub payload_hash: u64,
+    /// Byte offset of this fragment within the original packet, which stays
+    /// meaningful when a fragment is split again further along the path.
+    pub fragment: Option<usize>,
+    /// Node ids visited so far, starting at the source.
+    pub path: Vec<usize>,
+    /// Congestion Experienced: set by an ECN-enabled link whose queue was over threshold.
+    pub ecn_marked: bool,
+    /// Explicit hop list (source first) that overrides routing when present.
+    pub source_route: Option<Vec<usize>>,
+    /// Full source-to-member routes still served by this multicast copy; empty for unicast.
+    pub multicast_routes: Vec<Vec<usize>>,
+    /// Position within its (source, destination) flow, starting at 0.
+    pub sequence: u64,
+    /// Transfer this packet belongs to, for packets sent with `send_flow`.
+    pub flow_id: Option<u64>,
+    /// Time spent waiting behind earlier traffic in link queues, summed over hops.
+    pub queuing_delay: f64,
+}
+
+/// Out-of-order delivery counts. A packet is out of order if a later sequence
+/// number from the same flow was delivered before it; its extent is how far
+/// behind that highest sequence it arrived.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReorderStats {
+    pub delivered: usize,
+    pub out_of_order: usize,
+    pub max_extent: u64,
+}
+
+/// Bookkeeping for a packet that was split at an MTU boundary. The original is
+/// only delivered once fragments covering all of its bytes have arrived.
+#[derive(Debug, Clone)]
+struct Reassembly {
+    packet: DataPacket,
+    /// Offset and size of every distinct fragment received so far.
+    received: HashMap<usize, usize>,
+}
+
+/// A waiting packet a `WeightedFair` link pushed back to make room for a
+/// newer one: (packet id, fragment offset, delay added).
+type Displacement = (usize, Option<usize>, f64);
+
+/// A packet booked on a `WeightedFair` link that hasn't finished transmitting.
+#[derive(Debug, Clone)]
+pub struct WfqBooking {
+    /// Virtual finish time; waiting packets go on the wire in tag order.
+    pub tag: f64,
+    pub start: f64,
+    pub finish: f64,
+    /// (packet id, fragment offset) of the booked packet.
+    pub packet: (usize, Option<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkScheduler {
+    Fifo,
+    /// Higher `PacketType::priority` classes are always serviced first.
+    StrictPriority,
+    /// Backlogged flows share bandwidth in proportion to their weights.
+    WeightedFair,
+}
+
+/// Picks the next hop for a packet at `from` heading to `to`. Install one with
+/// `NetworkSimulation::set_routing_strategy`. Strategies must be `Clone` so
+/// a snapshot can carry its own copy.
+pub trait RoutingStrategy: CloneRoutingStrategy {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize>;
+
+    /// When `Some`, the simulation caches this strategy's answers and re-samples
+    /// link loads (see `NetworkSimulation::link_load`) at most this often.
+    fn refresh_interval(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Copies a boxed strategy; implemented for every `Clone` strategy.
+pub trait CloneRoutingStrategy {
+    fn clone_box(&self) -> Box<dyn RoutingStrategy>;
+}
+
+impl<T: RoutingStrategy + Clone + 'static> CloneRoutingStrategy for T {
+    fn clone_box(&self) -> Box<dyn RoutingStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn RoutingStrategy> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Fewest hops, ignoring latency and load. The default.
+#[derive(Clone)]
+pub struct BfsRouting;
+
+impl RoutingStrategy for BfsRouting {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize> {
+        sim.find_next_hop(from, to)
+    }
+}
+
+/// Lowest total propagation latency, however many hops that takes.
+#[derive(Clone)]
+pub struct DijkstraRouting;
+
+impl RoutingStrategy for DijkstraRouting {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize> {
+        sim.find_path_dijkstra(from, to)?.get(1).copied()
+    }
+}
+
+/// Lowest summed link `cost`, like OSPF with administrative weights. Matches
+/// `DijkstraRouting` until costs are changed.
+#[derive(Clone)]
+pub struct CostRouting;
+
+impl RoutingStrategy for CostRouting {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize> {
+        sim.shortest_path(from, to, 0.0f64, |cost, link| cost + link.cost)
+            .and_then(|path| path.get(1).copied())
+    }
+}
+
+/// Fewest AS boundary crossings, then lowest link `cost`, like BGP picking the
+/// shortest AS path before the IGP breaks ties. See `NetworkSimulation::policy_path`.
+#[derive(Clone)]
+pub struct PolicyRouting;
+
+impl RoutingStrategy for PolicyRouting {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize> {
+        sim.find_next_hop_policy(from, to)
+    }
+}
+
+/// Lowest `latency * (1 + load)`, where load is each link's offered load over
+/// the last `refresh_interval` seconds. Routes are cached and only recomputed
+/// once per interval.
+#[derive(Clone)]
+pub struct UtilizationAwareRouting {
+    pub refresh_interval: f64,
+}
+
+impl RoutingStrategy for UtilizationAwareRouting {
+    fn next_hop(&self, sim: &NetworkSimulation, from: usize, to: usize) -> Option<usize> {
+        sim.shortest_path(from, to, 0.0f64, |cost, link| cost + link.latency * (1.0 + sim.link_load(link.from, link.to)))
+            .and_then(|path| path.get(1).copied())
+    }
+
+    fn refresh_interval(&self) -> Option<f64> {
+        Some(self.refresh_interval)
+    }
+}
+
+/// The built-in routing choices, for `NetworkSimulation::set_routing_mode`.
+/// Each maps onto its `RoutingStrategy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoutingMode {
+    /// Fewest hops (BFS), ignoring load: `BfsRouting`.
+    HopCount,
+    /// Lowest `latency * (1 + load)`: `UtilizationAwareRouting`.
+    UtilizationAware { refresh_interval: f64 },
+}
+
+impl From<RoutingMode> for Box<dyn RoutingStrategy> {
+    fn from(mode: RoutingMode) -> Self {
+        match mode {
+            RoutingMode::HopCount => Box::new(BfsRouting),
+            RoutingMode::UtilizationAware { refresh_interval } => Box::new(UtilizationAwareRouting { refresh_interval }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplex {
+    Full,
+    /// Both directions share one channel, so a transmission one way blocks the other.
+    Half,
 }
 
 #[derive(Debug, Clone)]
@@ -67,27 +909,251 @@ pub struct NetworkLink {
     pub to: usize,
     pub distance: f64,
     pub latency: f64,
+    /// Administrative cost for `CostRouting`, OSPF-style. Starts at the
+    /// propagation delay in milliseconds; change it with `set_link_cost`.
+    pub cost: f64,
     pub bandwidth: f64,
+    /// (time threshold, bandwidth) pairs sorted by time, e.g. to model peak-hour congestion.
+    pub bandwidth_schedule: Vec<(f64, f64)>,
     pub queue_end_time: f64,
+    pub scheduler: LinkScheduler,
+    pub duplex: Duplex,
+    /// Backlog end per priority class, used by `LinkScheduler::StrictPriority`.
+    pub priority_queue_end: [f64; PRIORITY_LEVELS],
+    /// Virtual finish tag and weight of the last packet per (source,
+    /// destination) flow still backlogged in the fluid (GPS) reference of
+    /// `LinkScheduler::WeightedFair`.
+    pub flow_finish: HashMap<(usize, usize), (f64, f64)>,
+    /// GPS virtual time, in bits per unit weight, as of `virtual_time_at`.
+    pub virtual_time: f64,
+    pub virtual_time_at: f64,
+    /// Packets queued or on the wire under `LinkScheduler::WeightedFair`.
+    pub wfq_schedule: Vec<WfqBooking>,
+    pub bytes_sent: usize,
+    /// Of `bytes_sent`, what the link didn't lose on the way across.
+    pub bytes_delivered: usize,
+    pub busy_time: f64,
+    pub mtu: usize,
+    /// Probability (0-1) that a transmitted packet is lost.
+    pub loss_rate: f64,
+    /// Probability (0-1) that a transmitted packet arrives twice.
+    pub duplication_probability: f64,
+    /// Joules spent per bit transmitted.
+    pub energy_per_bit: f64,
+    /// Transit price in dollars per GB (10^9 bytes) sent; 0 for unmetered links.
+    pub cost_per_gb: f64,
+    /// Extra propagation delay drawn uniformly from [0, jitter) seconds per packet.
+    pub jitter: f64,
+    /// (start, end, extra seconds) windows during which packets sent onto the
+    /// link take longer to propagate, e.g. a transient lag spike.
+    pub latency_spikes: Vec<(f64, f64, f64)>,
+    /// Queue occupancy above which packets are ECN-marked; `None` disables marking.
+    pub ecn_threshold_bytes: Option<usize>,
+    /// False after an abrupt failure (`set_link_down`), once routing has converged.
+    pub up: bool,
+    /// (start, end) windows during which the link drains and takes no new traffic.
+    pub maintenance: Vec<(f64, f64)>,
+    /// Buffer size for tail drop; `None` means an unbounded queue.
+    pub queue_capacity_bytes: Option<usize>,
+    pub red: Option<RedConfig>,
+    /// Free-form labels ("subsea", "access", ...) for `stats_by_tag`.
+    pub tags: Vec<String>,
+    /// WAN deduplication: a whole packet whose `payload_hash` the link has
+    /// already carried crosses as a `DEDUP_REFERENCE_BYTES` reference.
+    pub dedup: bool,
+    pub seen_payloads: HashSet<u64>,
+    pub dedup_bytes_saved: usize,
 }
 
 impl NetworkLink {
-    pub fn new(from_id: usize, to_id: usize, dist: f64, bw: f64) -> Self {
-        let real_world_distance = dist * PATH_INEFFICIENCY_FACTOR;
-        let lat = real_world_distance / SPEED_IN_FIBER;
+    pub fn new(from_id: usize, to_id: usize, dist: f64, bw: f64, physics: &PhysicsConfig) -> Self {
+        Self::with_fiber_length(from_id, to_id, dist * physics.path_inefficiency_factor, bw, physics)
+    }
+
+    /// Builds a link whose fiber length is already known (e.g. summed through
+    /// cable landing points), so no winding factor is applied.
+    pub fn with_fiber_length(from_id: usize, to_id: usize, fiber_distance: f64, bw: f64, physics: &PhysicsConfig) -> Self {
+        let lat = fiber_distance / physics.speed_in_fiber;
         Self {
             from: from_id,
             to: to_id,
-            distance: real_world_distance,
+            distance: fiber_distance,
             latency: lat,
+            cost: lat * 1000.0,
             bandwidth: bw,
+            bandwidth_schedule: Vec::new(),
             queue_end_time: 0.0,
+            scheduler: LinkScheduler::Fifo,
+            duplex: Duplex::Full,
+            priority_queue_end: [0.0; PRIORITY_LEVELS],
+            flow_finish: HashMap::new(),
+            virtual_time: 0.0,
+            virtual_time_at: 0.0,
+            wfq_schedule: Vec::new(),
+            bytes_sent: 0,
+            bytes_delivered: 0,
+            busy_time: 0.0,
+            mtu: DEFAULT_MTU,
+            loss_rate: 0.0,
+            duplication_probability: 0.0,
+            energy_per_bit: DEFAULT_LINK_ENERGY_PER_BIT,
+            cost_per_gb: 0.0,
+            jitter: 0.0,
+            latency_spikes: Vec::new(),
+            ecn_threshold_bytes: None,
+            up: true,
+            maintenance: Vec::new(),
+            queue_capacity_bytes: None,
+            red: None,
+            tags: Vec::new(),
+            dedup: false,
+            seen_payloads: HashSet::new(),
+            dedup_bytes_saved: 0,
         }
     }
     
+    /// Time for a bit to cross the fiber, independent of packet size.
+    pub fn propagation_delay(&self) -> f64 {
+        self.latency
+    }
+
+    /// Time to push every bit of a packet onto the wire at the nominal bandwidth.
     pub fn transmission_time(&self, size: usize) -> f64 {
         (size as f64 * 8.0) / self.bandwidth
     }
+
+    /// Bandwidth in effect at `time`: the latest schedule entry whose threshold has
+    /// passed, or the nominal bandwidth before the first one.
+    pub fn effective_bandwidth(&self, time: f64) -> f64 {
+        self.bandwidth_schedule.iter()
+            .rev()
+            .find(|(threshold, _)| *threshold <= time)
+            .map_or(self.bandwidth, |(_, bw)| *bw)
+    }
+
+    /// Whether routing may put new packets on this link at `time`.
+    pub fn accepts_traffic(&self, time: f64) -> bool {
+        self.up && !self.maintenance.iter().any(|(start, end)| time >= *start && time < *end)
+    }
+
+    /// Extra propagation delay for a packet sent at `time` from any spike
+    /// windows covering it.
+    pub fn spike_delay(&self, time: f64) -> f64 {
+        self.latency_spikes.iter()
+            .filter(|(start, end, _)| time >= *start && time < *end)
+            .map(|(_, _, extra)| extra)
+            .sum()
+    }
+
+    /// Bytes `packet` takes on the wire, remembering its payload if the link
+    /// deduplicates. Fragments always go in full.
+    pub fn wire_size(&mut self, packet: &DataPacket) -> usize {
+        if !self.dedup || packet.payload_hash == 0 || packet.fragment.is_some() || self.seen_payloads.insert(packet.payload_hash) {
+            return packet.size_bytes;
+        }
+        let reference = DEDUP_REFERENCE_BYTES.min(packet.size_bytes);
+        self.dedup_bytes_saved += packet.size_bytes - reference;
+        reference
+    }
+
+    /// Bytes still waiting to be transmitted at `now`.
+    pub fn backlog_bytes(&self, now: f64) -> usize {
+        ((self.queue_end_time - now).max(0.0) * self.effective_bandwidth(now) / 8.0) as usize
+    }
+
+    pub fn transmission_time_at(&self, size: usize, time: f64) -> f64 {
+        (size as f64 * 8.0) / self.effective_bandwidth(time)
+    }
+
+    /// Books the wire for `packet` arriving at `now` and returns
+    /// (start_time, finish_time) for its transmission.
+    ///
+    /// Queues are modelled analytically by their backlog end times, so under strict
+    /// priority a high-priority packet only waits for equal-or-higher classes and
+    /// pushes back the backlog of lower classes. Lower-priority packets already
+    /// scheduled keep their original departure times.
+    ///
+    /// Weighted fair queuing stamps each packet with its virtual finish time in
+    /// the fluid (GPS) reference, where backlogged flows drain in proportion to
+    /// their weights, and sends waiting packets in tag order. `flow_weight` is
+    /// only used by WFQ.
+    pub fn reserve(&mut self, now: f64, packet: &DataPacket, flow_weight: f64) -> (f64, f64) {
+        let (start_time, finish_time, _) = self.book(now, packet, flow_weight);
+        (start_time, finish_time)
+    }
+
+    /// `reserve`, also returning the waiting WFQ packets the new one was
+    /// scheduled ahead of.
+    fn book(&mut self, now: f64, packet: &DataPacket, flow_weight: f64) -> (f64, f64, Vec<Displacement>) {
+        let size = packet.size_bytes;
+        let mut displaced = Vec::new();
+        let (start_time, finish_time) = match self.scheduler {
+            LinkScheduler::Fifo => {
+                let start = now.max(self.queue_end_time);
+                (start, start + self.transmission_time_at(size, start))
+            }
+            LinkScheduler::StrictPriority => {
+                let class = (packet.packet_type.priority() as usize).min(PRIORITY_LEVELS - 1);
+                let start = self.priority_queue_end[class..].iter().fold(now, |a, b| a.max(*b));
+                let trans_time = self.transmission_time_at(size, start);
+                for end in &mut self.priority_queue_end[..class] {
+                    if *end > start { *end += trans_time; }
+                }
+                self.priority_queue_end[class] = start + trans_time;
+                (start, start + trans_time)
+            }
+            LinkScheduler::WeightedFair => {
+                let weight = flow_weight.max(f64::MIN_POSITIVE);
+                self.advance_virtual_time(now);
+                let flow = (packet.source_id, packet.destination_id);
+                let start_tag = self.flow_finish.get(&flow).map_or(self.virtual_time, |(finish, _)| finish.max(self.virtual_time));
+                let tag = start_tag + size as f64 * 8.0 / weight;
+                self.flow_finish.insert(flow, (tag, weight));
+
+                // Goes after the packet on the wire and every waiting one with an
+                // earlier tag; waiting packets with later tags make room for it
+                self.wfq_schedule.retain(|b| b.finish > now);
+                let start = self.wfq_schedule.iter()
+                    .filter(|b| b.start <= now || b.tag <= tag)
+                    .fold(now, |end, b| end.max(b.finish));
+                let trans_time = self.transmission_time_at(size, start);
+                for booking in self.wfq_schedule.iter_mut().filter(|b| b.start > now && b.tag > tag) {
+                    booking.start += trans_time;
+                    booking.finish += trans_time;
+                    displaced.push((booking.packet.0, booking.packet.1, trans_time));
+                }
+                self.wfq_schedule.push(WfqBooking { tag, start, finish: start + trans_time, packet: (packet.id, packet.fragment) });
+                (start, start + trans_time)
+            }
+        };
+        let backlog_start = self.queue_end_time.max(now);
+        self.queue_end_time = backlog_start + self.transmission_time_at(size, backlog_start);
+        (start_time, finish_time, displaced)
+    }
+
+    /// Runs the GPS reference up to `now`: virtual time advances at the
+    /// bandwidth over the summed weight of flows still backlogged in it, and
+    /// flows whose last tag it passes drop out.
+    fn advance_virtual_time(&mut self, now: f64) {
+        let mut elapsed = now - self.virtual_time_at;
+        self.virtual_time_at = now;
+        while elapsed > 0.0 {
+            let (active_weight, next_finish) = self.flow_finish.values()
+                .filter(|(finish, _)| *finish > self.virtual_time)
+                .fold((0.0, f64::INFINITY), |(total, first), (finish, weight)| (total + weight, first.min(*finish)));
+            if active_weight <= 0.0 { break; }
+            let rate = self.effective_bandwidth(now) / active_weight;
+            let until_next = (next_finish - self.virtual_time) / rate;
+            if until_next >= elapsed {
+                self.virtual_time += elapsed * rate;
+                break;
+            }
+            self.virtual_time = next_finish;
+            elapsed -= until_next;
+        }
+        let virtual_time = self.virtual_time;
+        self.flow_finish.retain(|_, (finish, _)| *finish > virtual_time);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -95,17 +1161,26 @@ pub struct Event {
     pub time: f64,
     pub packet: DataPacket,
     pub event_type: EventType,
+    /// Insertion order, so events at the same time are processed first-in, first-out.
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum EventType {
+    ScheduledSend(usize),
     PacketArrival(usize),
     PacketTransmissionComplete(usize),
+    /// Fires at the sending node if the packet may need resending.
+    RetransmitTimeout(usize),
+    /// Fires at a receiver holding back the ACK for the packet's stream.
+    DelayedAck(usize),
+    /// Never queued: written to the event log when a node drops a packet.
+    Dropped(usize, DropReason),
 }
 
 impl PartialEq for Event {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
+        self.time == other.time && self.sequence == other.sequence
     }
 }
 
@@ -119,10 +1194,21 @@ impl PartialOrd for Event {
 
 impl Ord for Event {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.time.partial_cmp(&self.time).unwrap_or(Ordering::Equal)
+        // Reversed for BinaryHeap: earliest time first, then lowest sequence
+        other.time.partial_cmp(&self.time)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.sequence.cmp(&self.sequence))
     }
 }
 
+/// The complete state of a simulation at one instant, taken with
+/// `NetworkSimulation::snapshot`.
+#[derive(Clone)]
+pub struct SimSnapshot {
+    state: NetworkSimulation,
+}
+
+#[derive(Clone)]
 pub struct NetworkSimulation {
     pub servers: HashMap<usize, Server>,
     pub clients: HashMap<usize, Client>,
@@ -130,10 +1216,109 @@ pub struct NetworkSimulation {
     pub event_queue: BinaryHeap<Event>,
     pub current_time: f64,
     pub completed_packets: Vec<(DataPacket, f64)>,
+    pub dropped_packets: Vec<(DataPacket, DropReason)>,
+    pub verbose: bool,
+    pub flow_weights: HashMap<(usize, usize), f64>,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    routing: Box<dyn RoutingStrategy>,
+    /// Constants for link lengths and latencies; set before connecting nodes.
+    pub physics: PhysicsConfig,
+    /// Next hop per (node, destination) for strategies with a refresh interval.
+    route_cache: HashMap<(usize, usize), Option<usize>>,
+    route_cache_time: f64,
+    /// Serve next hops from `route_cache` whatever the strategy, e.g. after
+    /// `precompute_routes`. Pairs missing from the cache are still computed
+    /// on demand.
+    pub use_precomputed_routes: bool,
+    /// Each link's `busy_time` at the last route refresh and its load over the
+    /// interval before it.
+    busy_at_refresh: HashMap<(usize, usize), (f64, f64)>,
+    /// When set, every packet leaving its source (as a `ScheduledSend`), every
+    /// processed event and every drop is appended to `event_log`.
+    pub record_events: bool,
+    pub event_log: Vec<Event>,
+    /// Reliable delivery is off while this is `None`.
+    pub retransmit: Option<RetransmitPolicy>,
+    pub retransmissions: usize,
+    /// Copies discarded at their destination because that (flow, sequence
+    /// number) had already been delivered, or that fragment had already arrived.
+    pub duplicates_detected: usize,
+    /// Completed TCP handshakes, in the order they finished.
+    pub connections: Vec<ConnectionEstablished>,
+    /// Cap on `event_queue`; `None` leaves it unbounded. A packet that would be
+    /// queued past it (new send, next hop, fragment or reply) is dropped as
+    /// `AdmissionControl`, and a timer is discarded.
+    pub max_events: Option<usize>,
+    /// Most links a packet may cross, as an IP TTL; `None` leaves it unbounded.
+    /// A packet that would need another hop is dropped as `HopLimitExceeded`,
+    /// which keeps one caught in a routing loop from circling forever.
+    pub hop_limit: Option<usize>,
+    /// Seconds routing takes to notice a `set_link_down` failure, as with BGP
+    /// withdrawal. Until then next hops still point at the dead link and
+    /// packets sent over it are dropped with `DropReason::LinkDown`.
+    pub convergence_delay: f64,
+    /// Failed links routing hasn't caught up with yet, and when it will.
+    unconverged_failures: HashMap<(usize, usize), f64>,
+    /// Query send time per (client, server) whose DNS lookup is outstanding.
+    pending_lookups: HashMap<(usize, usize), f64>,
+    /// Lookup time per (client, server), kept until the handshake completes.
+    dns_times: HashMap<(usize, usize), f64>,
+    /// SYN send time per (client, server) whose handshake is still in progress.
+    pending_handshakes: HashMap<(usize, usize), f64>,
+    events_processed: usize,
+    in_flight: usize,
+    /// High-water mark of `in_flight`.
+    peak_in_flight: usize,
+    next_packet_id: usize,
+    next_event_sequence: u64,
+    next_sequence: HashMap<(usize, usize), u64>,
+    /// (source, destination, sequence) of every packet delivered whole.
+    received_sequences: HashSet<(usize, usize, u64)>,
+    tcp_options: HashMap<(usize, usize), TcpOptions>,
+    tcp_streams: HashMap<(usize, usize), TcpStream>,
+    /// Packets sent so far per flow id, to tell complete flows from partial ones.
+    flow_packets: HashMap<u64, usize>,
+    reassembly: HashMap<usize, Reassembly>,
+    /// (request id, sent at) per (requester, server, content_id), oldest first, until answered.
+    open_cdn_requests: HashMap<(usize, usize, u64), VecDeque<(usize, f64)>>,
+    /// Time to first byte per answered CDN request id.
+    ttfb_by_request: HashMap<usize, f64>,
+    /// Path each CDN request took to its server, and the path its response took back once delivered.
+    request_paths: HashMap<usize, (Vec<usize>, Option<Vec<usize>>)>,
+    /// Bitrate (bps) promised to admitted streams per (from, to) link.
+    stream_reservations: HashMap<(usize, usize), f64>,
+    /// Clients waiting on an edge's origin fetch, keyed by (edge, content_id).
+    pending_fetches: HashMap<(usize, u64), Vec<usize>>,
+    /// Retries used per reliably-sent packet id that hasn't been delivered yet.
+    unacked: HashMap<usize, u32>,
+    /// Ids of packets with a copy in `dropped_packets`.
+    dropped_ids: HashSet<usize>,
+    /// Weighted uplinks per node, from `set_uplink_weights`.
+    uplink_weights: HashMap<usize, Vec<(usize, u32)>>,
+    /// Smooth weighted round-robin credit per (node, uplink).
+    uplink_credit: HashMap<(usize, usize), i64>,
+    /// Uplink pinned to each (node, source, destination) flow.
+    flow_uplinks: HashMap<(usize, usize, usize), usize>,
+    /// Seed last given to `set_seed`, which `reset` starts the RNGs from again.
+    seed: u64,
+    rng: SimRng,
+    /// Draws for packet arrivals and sizes in traffic generators.
+    traffic_rng: SimRng,
+}
+
+impl Default for NetworkSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NetworkSimulation {
     pub fn new() -> Self {
+        Self::with_config(PhysicsConfig::default())
+    }
+
+    pub fn with_config(physics: PhysicsConfig) -> Self {
         Self {
             servers: HashMap::new(),
             clients: HashMap::new(),
@@ -141,9 +1326,124 @@ impl NetworkSimulation {
             event_queue: BinaryHeap::new(),
             current_time: 0.0,
             completed_packets: Vec::new(),
+            dropped_packets: Vec::new(),
+            verbose: true,
+            flow_weights: HashMap::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+            routing: Box::new(BfsRouting),
+            physics,
+            route_cache: HashMap::new(),
+            route_cache_time: f64::NEG_INFINITY,
+            use_precomputed_routes: false,
+            busy_at_refresh: HashMap::new(),
+            record_events: false,
+            event_log: Vec::new(),
+            retransmit: None,
+            retransmissions: 0,
+            duplicates_detected: 0,
+            connections: Vec::new(),
+            max_events: None,
+            hop_limit: None,
+            convergence_delay: 0.0,
+            unconverged_failures: HashMap::new(),
+            pending_lookups: HashMap::new(),
+            dns_times: HashMap::new(),
+            pending_handshakes: HashMap::new(),
+            events_processed: 0,
+            in_flight: 0,
+            peak_in_flight: 0,
+            next_packet_id: 0,
+            next_event_sequence: 0,
+            next_sequence: HashMap::new(),
+            received_sequences: HashSet::new(),
+            tcp_options: HashMap::new(),
+            tcp_streams: HashMap::new(),
+            flow_packets: HashMap::new(),
+            reassembly: HashMap::new(),
+            open_cdn_requests: HashMap::new(),
+            ttfb_by_request: HashMap::new(),
+            request_paths: HashMap::new(),
+            stream_reservations: HashMap::new(),
+            pending_fetches: HashMap::new(),
+            unacked: HashMap::new(),
+            dropped_ids: HashSet::new(),
+            uplink_weights: HashMap::new(),
+            uplink_credit: HashMap::new(),
+            flow_uplinks: HashMap::new(),
+            seed: DEFAULT_SEED,
+            rng: SimRng::new(DEFAULT_SEED),
+            traffic_rng: SimRng::new(DEFAULT_SEED ^ TRAFFIC_STREAM),
         }
     }
 
+    /// Deep copy of everything: topology, pending events, clock, counters,
+    /// results, routing strategy and RNG state, so a run resumed from
+    /// `restore` replays exactly as it would have from here.
+    pub fn snapshot(&self) -> SimSnapshot {
+        SimSnapshot { state: self.clone() }
+    }
+
+    /// Puts the simulation back to the moment `snapshot` was taken. The
+    /// snapshot is left intact, so several branches can start from it.
+    pub fn restore(&mut self, snapshot: &SimSnapshot) {
+        *self = snapshot.state.clone();
+    }
+
+    /// Starts over at time 0 with the same topology and settings: servers,
+    /// clients, links and their configuration, routing and policies are kept,
+    /// while queued events, results, counters, edge caches and all per-run
+    /// bookkeeping (link queues, connections, reservations, ...) are cleared
+    /// and the RNG restarts from its seed. A run after `reset` therefore
+    /// matches the same run on a freshly built simulation. Failures still
+    /// waiting on `convergence_delay` take effect immediately.
+    pub fn reset(&mut self) {
+        for (from, to) in std::mem::take(&mut self.unconverged_failures).into_keys() {
+            if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+                link.up = false;
+            }
+        }
+        for link in &mut self.links {
+            link.queue_end_time = 0.0;
+            link.priority_queue_end = [0.0; PRIORITY_LEVELS];
+            link.flow_finish.clear();
+            link.virtual_time = 0.0;
+            link.virtual_time_at = 0.0;
+            link.wfq_schedule.clear();
+            link.bytes_sent = 0;
+            link.bytes_delivered = 0;
+            link.busy_time = 0.0;
+            link.seen_payloads.clear();
+            link.dedup_bytes_saved = 0;
+        }
+        for server in self.servers.values_mut() {
+            server.cache.clear();
+        }
+
+        let previous = std::mem::replace(self, NetworkSimulation::with_config(self.physics));
+        self.servers = previous.servers;
+        self.clients = previous.clients;
+        self.links = previous.links;
+        self.verbose = previous.verbose;
+        self.flow_weights = previous.flow_weights;
+        self.routing = previous.routing;
+        self.use_precomputed_routes = previous.use_precomputed_routes;
+        self.record_events = previous.record_events;
+        self.retransmit = previous.retransmit;
+        self.max_events = previous.max_events;
+        self.hop_limit = previous.hop_limit;
+        self.convergence_delay = previous.convergence_delay;
+        self.tcp_options = previous.tcp_options;
+        self.uplink_weights = previous.uplink_weights;
+        self.set_seed(previous.seed);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = SimRng::new(seed);
+        self.traffic_rng = SimRng::new(seed ^ TRAFFIC_STREAM);
+    }
+
     pub fn add_server(&mut self, server: Server) {
         self.servers.insert(server.id, server);
     }
@@ -151,23 +1451,113 @@ impl NetworkSimulation {
     pub fn add_client(&mut self, client: Client) {
         self.clients.insert(client.id, client);
     }
+
+    /// Takes a server offline: its links are removed and packets addressed to
+    /// it, arriving at it or about to leave it are dropped with
+    /// `DropReason::NodeRemoved`.
+    pub fn remove_server(&mut self, id: usize) -> Option<Server> {
+        let server = self.servers.remove(&id)?;
+        self.detach_node(id);
+        Some(server)
+    }
+
+    /// As `remove_server`, for a client.
+    pub fn remove_client(&mut self, id: usize) -> Option<Client> {
+        let client = self.clients.remove(&id)?;
+        self.detach_node(id);
+        Some(client)
+    }
+
+    pub fn update_server_bandwidth(&mut self, id: usize, bandwidth: f64) {
+        if let Some(server) = self.servers.get_mut(&id) {
+            server.bandwidth = bandwidth;
+        }
+    }
+
+    fn detach_node(&mut self, id: usize) {
+        self.links.retain(|l| l.from != id && l.to != id);
+        self.unconverged_failures.retain(|(from, to), _| *from != id && *to != id);
+        self.route_cache.clear();
+
+        let (lost, kept): (Vec<Event>, Vec<Event>) = std::mem::take(&mut self.event_queue)
+            .into_iter()
+            .partition(|e| e.packet.destination_id == id || match e.event_type {
+                EventType::ScheduledSend(node)
+                | EventType::PacketArrival(node)
+                | EventType::PacketTransmissionComplete(node)
+                | EventType::RetransmitTimeout(node)
+                | EventType::DelayedAck(node) => node == id,
+                EventType::Dropped(..) => false,
+            });
+        self.event_queue = kept.into_iter().collect();
+        for event in lost {
+            match event.event_type {
+                // Timers, not packets
+                EventType::RetransmitTimeout(_) | EventType::DelayedAck(_) => {}
+                EventType::PacketArrival(_) => {
+                    self.in_flight -= 1;
+                    self.record_drop(event.packet, DropReason::NodeRemoved);
+                }
+                _ => self.record_drop(event.packet, DropReason::NodeRemoved),
+            }
+        }
+    }
     
     pub fn connect_nodes(&mut self, from_id: usize, to_id: usize, bandwidth: f64) {
         let distance = self.calculate_distance(from_id, to_id);
-        let link = NetworkLink::new(from_id, to_id, distance, bandwidth);
+        let link = NetworkLink::new(from_id, to_id, distance, bandwidth, &self.physics);
         
-        println!(
-            "Linking {} ↔ {} | Physical Gap: {:.0} km | Actual Fiber: {:.0} km | Min RTT: {:.2} ms",
-            self.get_node_name(from_id),
-            self.get_node_name(to_id),
-            distance / 1000.0,
-            link.distance / 1000.0,
-            (link.latency * 2.0) * 1000.0
-        );
+        if self.verbose {
+            let midpoint = self.link_midpoint(from_id, to_id);
+            println!(
+                "Linking {} ↔ {} | Physical Gap: {:.0} km | Actual Fiber: {:.0} km | Min RTT: {:.2} ms | Midpoint: {:.2}, {:.2}",
+                self.get_node_name(from_id),
+                self.get_node_name(to_id),
+                distance / 1000.0,
+                link.distance / 1000.0,
+                (link.latency * 2.0) * 1000.0,
+                midpoint.latitude,
+                midpoint.longitude
+            );
+        }
         
         self.links.push(link);
     }
 
+    /// Links two nodes along a cable that passes through `waypoints` (landing
+    /// points, junctions). The fiber length is the sum of great-circle legs,
+    /// replacing the flat `PATH_INEFFICIENCY_FACTOR` estimate.
+    pub fn connect_nodes_via(&mut self, from_id: usize, to_id: usize, waypoints: &[GeoLocation], bandwidth: f64) {
+        let direct = self.calculate_distance(from_id, to_id);
+        let from_loc = self.location_of(from_id).expect("Source node not found").clone();
+        let to_loc = self.location_of(to_id).expect("Destination node not found").clone();
+
+        let route: Vec<&GeoLocation> = std::iter::once(&from_loc).chain(waypoints).chain(std::iter::once(&to_loc)).collect();
+        let fiber_distance: f64 = route.windows(2).map(|leg| leg[0].distance_with(leg[1], &self.physics)).sum();
+        let link = NetworkLink::with_fiber_length(from_id, to_id, fiber_distance, bandwidth, &self.physics);
+
+        if self.verbose {
+            println!(
+                "Linking {} ↔ {} via {} | Physical Gap: {:.0} km | Actual Fiber: {:.0} km | Min RTT: {:.2} ms",
+                self.get_node_name(from_id),
+                self.get_node_name(to_id),
+                waypoints.iter().map(|w| w.name.as_str()).collect::<Vec<_>>().join(", "),
+                direct / 1000.0,
+                link.distance / 1000.0,
+                (link.latency * 2.0) * 1000.0
+            );
+        }
+
+        self.links.push(link);
+    }
+
+    /// Links a client and server with different bandwidth in each direction,
+    /// e.g. residential or satellite access where download dwarfs upload.
+    pub fn connect_asymmetric(&mut self, a: usize, b: usize, up_bps: f64, down_bps: f64) {
+        self.connect_nodes(a, b, up_bps);
+        self.connect_nodes(b, a, down_bps);
+    }
+
     pub fn get_node_name(&self, id: usize) -> String {
         if let Some(s) = self.servers.get(&id) {
             s.location.name.clone()
@@ -178,6 +1568,20 @@ impl NetworkSimulation {
         }
     }
 
+    /// Looks up a node by its location name. Returns `None` if no node, or more
+    /// than one node, carries that name.
+    pub fn node_id_by_name(&self, name: &str) -> Option<usize> {
+        let mut matches = self.all_nodes().filter(|(_, loc)| loc.name == name).map(|(id, _)| id);
+        let id = matches.next()?;
+        if matches.next().is_some() { None } else { Some(id) }
+    }
+
+    /// Every server and client in the topology, in no particular order.
+    pub fn all_nodes(&self) -> impl Iterator<Item = (usize, &GeoLocation)> {
+        self.servers.values().map(|s| (s.id, &s.location))
+            .chain(self.clients.values().map(|c| (c.id, &c.location)))
+    }
+
     pub fn find_next_hop(&self, from: usize, to: usize) -> Option<usize> {
         let mut queue = VecDeque::new();
         queue.push_back((from, None));
@@ -189,7 +1593,7 @@ impl NetworkSimulation {
                 return first_hop;
             }
 
-            for link in self.links.iter().filter(|l| l.from == current) {
+            for link in self.links.iter().filter(|l| l.from == current && l.accepts_traffic(self.current_time)) {
                 if !visited.contains(&link.to) {
                     visited.insert(link.to);
                     let next_hop = if first_hop.is_none() { Some(link.to) } else { first_hop };
@@ -200,116 +1604,2253 @@ impl NetworkSimulation {
         None
     }
 
-    pub fn calculate_distance(&self, from_id: usize, to_id: usize) -> f64 {
-        let get_loc = |id| {
-            if let Some(s) = self.servers.get(&id) { Some(&s.location) }
-            else { self.clients.get(&id).map(|c| &c.location) }
-        };
+    /// Every loop-free path from `from` to `to` of at most `max_hops` links,
+    /// over links currently accepting traffic, by depth-first search.
+    pub fn all_paths(&self, from: usize, to: usize, max_hops: usize) -> Vec<Vec<usize>> {
+        let mut paths = Vec::new();
+        self.extend_paths(&mut vec![from], to, max_hops, &mut paths);
+        paths
+    }
 
-        let from_loc = get_loc(from_id).expect("Source node not found");
-        let to_loc = get_loc(to_id).expect("Destination node not found");
-        
-        from_loc.distance_to(to_loc)
+    fn extend_paths(&self, path: &mut Vec<usize>, to: usize, max_hops: usize, paths: &mut Vec<Vec<usize>>) {
+        let Some(&current) = path.last() else { return };
+        if current == to {
+            paths.push(path.clone());
+            return;
+        }
+        if path.len() > max_hops { return; }
+        for link in self.links.iter().filter(|l| l.from == current && l.accepts_traffic(self.current_time)) {
+            if path.contains(&link.to) { continue; }
+            path.push(link.to);
+            self.extend_paths(path, to, max_hops, paths);
+            path.pop();
+        }
     }
 
-    pub fn send_packet_ex(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType) {
-        let packet = DataPacket {
-            id: self.completed_packets.len() + self.event_queue.len(),
-            source_id: from,
-            destination_id: to,
-            size_bytes,
-            created_at: self.current_time,
-            packet_type: p_type,
-        };
-        
-        if let Some(next_hop) = self.find_next_hop(from, to) {
-            let current_time = self.current_time;
-            if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == next_hop) {
-                let trans_time = link.transmission_time(size_bytes);
-                let start_time = current_time.max(link.queue_end_time);
-                let arrival_time = start_time + link.latency + trans_time;
-                link.queue_end_time = start_time + trans_time;
-                
-                self.event_queue.push(Event {
-                    time: arrival_time,
-                    packet,
-                    event_type: EventType::PacketArrival(next_hop),
-                });
+    /// Path diversity: how many paths from `from` to `to` exist that share no
+    /// link, i.e. how many link failures it takes to cut them apart. Computed
+    /// as a unit-capacity max flow (Edmonds-Karp).
+    pub fn edge_disjoint_paths(&self, from: usize, to: usize) -> usize {
+        if from == to { return 0; }
+        let mut residual: HashMap<(usize, usize), usize> = HashMap::new();
+        for link in self.links.iter().filter(|l| l.accepts_traffic(self.current_time)) {
+            *residual.entry((link.from, link.to)).or_insert(0) += 1;
+            residual.entry((link.to, link.from)).or_insert(0);
+        }
+        let mut adjacency: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &(a, b) in residual.keys() {
+            adjacency.entry(a).or_default().push(b);
+        }
+
+        let mut flow = 0;
+        loop {
+            let mut previous: HashMap<usize, usize> = HashMap::new();
+            let mut queue = VecDeque::from([from]);
+            while let Some(node) = queue.pop_front() {
+                if node == to { break; }
+                for &next in adjacency.get(&node).into_iter().flatten() {
+                    if next != from && !previous.contains_key(&next) && residual.get(&(node, next)).is_some_and(|c| *c > 0) {
+                        previous.insert(next, node);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            if !previous.contains_key(&to) { return flow; }
+
+            let mut node = to;
+            while let Some(&prev) = previous.get(&node) {
+                if let Some(capacity) = residual.get_mut(&(prev, node)) { *capacity -= 1; }
+                *residual.entry((node, prev)).or_insert(0) += 1;
+                node = prev;
             }
+            flow += 1;
         }
     }
 
-    pub fn run_simulation(&mut self, duration: f64) {
-        while let Some(event) = self.event_queue.pop() {
-            if event.time > duration { break; }
-            self.current_time = event.time;
-            
-            match event.event_type {
-                EventType::PacketArrival(node_id) => {
-                    if node_id == event.packet.destination_id {
-                        let latency = self.current_time - event.packet.created_at;
-                        println!("[{:.4}s] {:?} packet (ID {}) arrived at {} | Latency: {:.2} ms", 
-                                 self.current_time, event.packet.packet_type, event.packet.id, self.get_node_name(node_id), latency * 1000.0);
-                        
-                        match event.packet.packet_type {
-                            PacketType::TcpSyn => {
-                                self.send_packet_ex(node_id, event.packet.source_id, 64, PacketType::TcpSynAck);
-                            }
-                            PacketType::TcpSynAck => {
-                                self.send_packet_ex(node_id, event.packet.source_id, 64, PacketType::TcpAck);
-                            }
-                            PacketType::CdnRequest => {
-                                // [Sepedi]: Ge resepi entle ya CDN e fihla, re araba kapee-pee
-                                // CDN server responds immediately with the cached data (1KB for demo)
-                                self.send_packet_ex(node_id, event.packet.source_id, 1024, PacketType::CdnResponse);
-                            }
-                            _ => {}
-                        }
-                        
-                        self.completed_packets.push((event.packet, latency));
-                    } else {
-                        let delay = self.servers.get(&node_id).map(|s| s.processing_delay).unwrap_or(0.0);
-                        self.event_queue.push(Event {
-                            time: self.current_time + delay,
-                            packet: event.packet,
-                            event_type: EventType::PacketTransmissionComplete(node_id),
-                        });
-                    }
+    /// Dijkstra over directed links. `edge_cost` folds a link into the running
+    /// path cost, so any ordered cost (plain latency, lexicographic tuples, ...)
+    /// can be minimised. Returns the full node path including both endpoints.
+    fn shortest_path<C, F>(&self, from: usize, to: usize, zero: C, edge_cost: F) -> Option<Vec<usize>>
+    where
+        C: Copy + PartialOrd,
+        F: Fn(C, &NetworkLink) -> C,
+    {
+        let mut best: HashMap<usize, C> = HashMap::new();
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut settled = HashSet::new();
+        best.insert(from, zero);
+
+        loop {
+            let (current, cost) = best.iter()
+                .filter(|(node, _)| !settled.contains(*node))
+                .fold(None, |acc: Option<(usize, C)>, (node, cost)| match acc {
+                    Some((_, c)) if c <= *cost => acc,
+                    _ => Some((*node, *cost)),
+                })?;
+            if current == to { break; }
+            settled.insert(current);
+
+            for link in self.links.iter().filter(|l| l.from == current && !settled.contains(&l.to) && l.accepts_traffic(self.current_time)) {
+                let candidate = edge_cost(cost, link);
+                if best.get(&link.to).is_none_or(|c| candidate < *c) {
+                    best.insert(link.to, candidate);
+                    previous.insert(link.to, current);
                 }
-                EventType::PacketTransmissionComplete(node_id) => {
-                    if let Some(next_hop) = self.find_next_hop(node_id, event.packet.destination_id) {
-                        let current_time = self.current_time;
-                        let size = event.packet.size_bytes;
-                        if let Some(link) = self.links.iter_mut().find(|l| l.from == node_id && l.to == next_hop) {
-                            let trans_time = link.transmission_time(size);
-                            let start_time = current_time.max(link.queue_end_time);
-                            let arrival_time = start_time + link.latency + trans_time;
-                            link.queue_end_time = start_time + trans_time;
-                            
-                            self.event_queue.push(Event {
-                                time: arrival_time,
-                                packet: event.packet,
-                                event_type: EventType::PacketArrival(next_hop),
-                            });
-                        }
-                    }
+            }
+        }
+
+        let mut path = vec![to];
+        while let Some(prev) = previous.get(path.last()?) {
+            path.push(*prev);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// AS of a node: its own for servers, that of its first-hop server for clients.
+    pub fn as_number_of(&self, id: usize) -> Option<u32> {
+        if let Some(s) = self.servers.get(&id) {
+            return Some(s.as_number);
+        }
+        self.links.iter()
+            .filter(|l| l.from == id)
+            .find_map(|l| self.servers.get(&l.to).map(|s| s.as_number))
+    }
+
+    /// Inter-domain route: fewest AS boundary crossings first, then lowest summed
+    /// link `cost` (the latency in milliseconds unless changed with `set_link_cost`).
+    pub fn policy_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        self.shortest_path(from, to, (0u32, 0.0f64), |(as_hops, cost), link| {
+            let crossing = self.as_number_of(link.from) != self.as_number_of(link.to);
+            (as_hops + crossing as u32, cost + link.cost)
+        })
+    }
+
+    /// Replaces the routing strategy; cached routes from the old one are discarded.
+    pub fn set_routing_strategy(&mut self, strategy: Box<dyn RoutingStrategy>) {
+        self.routing = strategy;
+        self.route_cache.clear();
+        self.route_cache_time = f64::NEG_INFINITY;
+    }
+
+    /// Installs the strategy behind one of the built-in routing modes.
+    pub fn set_routing_mode(&mut self, mode: RoutingMode) {
+        self.set_routing_strategy(mode.into());
+    }
+
+    /// Next hop under the active routing strategy.
+    pub fn route_next_hop(&mut self, from: usize, to: usize) -> Option<usize> {
+        if !self.unconverged_failures.is_empty() {
+            self.converge_routes();
+        }
+        let refresh_interval = self.routing.refresh_interval();
+        if refresh_interval.is_some_and(|interval| self.current_time - self.route_cache_time >= interval) {
+            self.refresh_link_loads();
+        }
+        if refresh_interval.is_some() || self.use_precomputed_routes {
+            if let Some(next) = self.route_cache.get(&(from, to)) {
+                let still_usable = next.is_none_or(|next| self.get_link(from, next).is_some_and(|l| l.accepts_traffic(self.current_time)));
+                if still_usable { return *next; }
+            }
+        }
+
+        let next = self.routing.next_hop(self, from, to);
+        if refresh_interval.is_some() {
+            self.route_cache.insert((from, to), next);
+        }
+        next
+    }
+
+    /// Fills `route_cache` with the installed strategy's next hop for every
+    /// pair of nodes at once and turns on `use_precomputed_routes`. Worth it on
+    /// large static topologies, where it replaces a search per packet. Anything
+    /// that clears the cache (a new strategy, cost changes, node removal, a
+    /// caching strategy's refresh) falls back to on-demand routing.
+    pub fn precompute_routes(&mut self) {
+        let nodes = self.node_order();
+        let mut routes = Vec::new();
+        for &from in &nodes {
+            for &to in nodes.iter().filter(|&&to| to != from) {
+                if let Some(hop) = self.routing.next_hop(self, from, to) {
+                    routes.push(((from, to), Some(hop)));
                 }
             }
         }
+        self.route_cache.extend(routes);
+        self.use_precomputed_routes = true;
     }
 
-    pub fn analyze_results(&self) {
-        println!("\n=== Simulation Results ===");
-        if self.completed_packets.is_empty() { return; }
-        
-        let avg_latency = self.completed_packets.iter().map(|(_, l)| *l).sum::<f64>() / self.completed_packets.len() as f64;
-        let max_lat = self.completed_packets.iter().map(|(_, l)| *l).fold(0.0f64, |a, b| a.max(b));
-        
-        let total_capacity: f64 = self.servers.values().map(|s| s.bandwidth).sum();
+    /// Fraction of the time the link was busy over the interval before the last
+    /// route refresh; 0 until a caching strategy has refreshed twice.
+    pub fn link_load(&self, from: usize, to: usize) -> f64 {
+        self.busy_at_refresh.get(&(from, to)).map_or(0.0, |(_, load)| *load)
+    }
+
+    /// Snapshots each link's offered load since the previous refresh and drops
+    /// cached routes so they are recomputed against it.
+    fn refresh_link_loads(&mut self) {
+        let elapsed = self.current_time - self.route_cache_time;
+        for link in &self.links {
+            // The first refresh (or a link added since) only sets the baseline
+            let load = match self.busy_at_refresh.get(&(link.from, link.to)) {
+                Some((previous, _)) if elapsed.is_finite() && elapsed > 0.0 => (link.busy_time - previous) / elapsed,
+                _ => 0.0,
+            };
+            self.busy_at_refresh.insert((link.from, link.to), (link.busy_time, load));
+        }
+        self.route_cache.clear();
+        self.route_cache_time = self.current_time;
+    }
+
+    /// First hop of `policy_path`; what `PolicyRouting` forwards along.
+    pub fn find_next_hop_policy(&self, from: usize, to: usize) -> Option<usize> {
+        self.policy_path(from, to)?.get(1).copied()
+    }
+
+    pub fn location_of(&self, id: usize) -> Option<&GeoLocation> {
+        if let Some(s) = self.servers.get(&id) { Some(&s.location) }
+        else { self.clients.get(&id).map(|c| &c.location) }
+    }
+
+    /// Point on the great circle halfway between two nodes, e.g. to place a
+    /// relay or amplifier site.
+    pub fn link_midpoint(&self, from_id: usize, to_id: usize) -> GeoLocation {
+        let from_loc = self.location_of(from_id).expect("Source node not found");
+        let to_loc = self.location_of(to_id).expect("Destination node not found");
+        from_loc.interpolate(to_loc, 0.5)
+    }
+
+    pub fn calculate_distance(&self, from_id: usize, to_id: usize) -> f64 {
+        let from_loc = self.location_of(from_id).expect("Source node not found");
+        let to_loc = self.location_of(to_id).expect("Destination node not found");
         
-        println!("Total delivered: {}", self.completed_packets.len());
-        println!("Total Capacity: {:.2} Gbps", total_capacity / 1_000_000_000.0);
-        println!("Avg Latency: {:.2} ms", avg_latency * 1000.0);
-        println!("Max Latency: {:.2} ms", max_lat * 1000.0);
+        from_loc.distance_with(to_loc, &self.physics)
+    }
+
+    /// Absolute physical lower bound on one-way latency: light in vacuum along the
+    /// route with the shortest great-circle hop distances, ignoring fiber index,
+    /// cable winding and queuing. Infinite if `to` is unreachable.
+    pub fn theoretical_min_latency(&self, from: usize, to: usize) -> f64 {
+        let Some(path) = self.shortest_path(from, to, 0.0f64, |dist, link| {
+            dist + self.calculate_distance(link.from, link.to)
+        }) else {
+            return f64::INFINITY;
+        };
+        let distance: f64 = path.windows(2).map(|hop| self.calculate_distance(hop[0], hop[1])).sum();
+        distance / SPEED_OF_LIGHT
+    }
+
+    pub fn get_link(&self, from: usize, to: usize) -> Option<&NetworkLink> {
+        self.links.iter().find(|l| l.from == from && l.to == to)
+    }
+
+    /// How long a packet handed to the `from` -> `to` link right now would wait
+    /// before its first bit is sent.
+    pub fn link_queue_delay(&self, from: usize, to: usize) -> Option<f64> {
+        self.get_link(from, to).map(|link| (link.queue_end_time - self.current_time).max(0.0))
+    }
+
+    /// Graceful maintenance: from `start` to `end` the link admits no new
+    /// packets, so routing steers around it, while anything already queued or
+    /// in flight on it still arrives.
+    pub fn schedule_maintenance(&mut self, from: usize, to: usize, start: f64, end: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.maintenance.push((start, end));
+        }
+    }
+
+    /// Adds `extra_ms` milliseconds of propagation delay to packets sent onto
+    /// the link from `start` to `end`. Routing keeps seeing the normal latency.
+    pub fn add_latency_spike(&mut self, from: usize, to: usize, extra_ms: f64, start: f64, end: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.latency_spikes.push((start, end, extra_ms.max(0.0) / 1000.0));
+        }
+    }
+
+    /// Abrupt failure: the link stops carrying traffic now and everything in
+    /// flight on it is dropped with `DropReason::LinkDown`. Routing only steers
+    /// around it after `convergence_delay`.
+    pub fn set_link_down(&mut self, from: usize, to: usize) {
+        let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) else { return };
+        if self.convergence_delay > 0.0 {
+            // Still looks up to routing; `transmit` drops what's sent its way
+            self.unconverged_failures.insert((from, to), self.current_time + self.convergence_delay);
+        } else {
+            link.up = false;
+        }
+        link.queue_end_time = link.queue_end_time.min(self.current_time);
+
+        let (lost, kept): (Vec<Event>, Vec<Event>) = std::mem::take(&mut self.event_queue)
+            .into_iter()
+            .partition(|e| matches!(e.event_type, EventType::PacketArrival(node) if node == to) && e.packet.path.last() == Some(&from));
+        self.event_queue = kept.into_iter().collect();
+        self.in_flight -= lost.len();
+        for event in lost {
+            self.record_drop(event.packet, DropReason::LinkDown);
+        }
+    }
+
+    pub fn set_link_up(&mut self, from: usize, to: usize) {
+        self.unconverged_failures.remove(&(from, to));
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.up = true;
+        }
+    }
+
+    /// Takes down, as far as routing is concerned, every failed link whose
+    /// convergence delay has run out.
+    fn converge_routes(&mut self) {
+        let now = self.current_time;
+        let converged: Vec<(usize, usize)> = self.unconverged_failures.iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(link, _)| *link)
+            .collect();
+        if converged.is_empty() { return; }
+        for (from, to) in converged {
+            self.unconverged_failures.remove(&(from, to));
+            if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+                link.up = false;
+            }
+        }
+        self.route_cache.clear();
+    }
+
+    pub fn set_link_scheduler(&mut self, from: usize, to: usize, scheduler: LinkScheduler) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.scheduler = scheduler;
+        }
+    }
+
+    /// TCP behaviours for the `from` -> `to` stream; both ends read the same options.
+    pub fn set_tcp_options(&mut self, from: usize, to: usize, options: TcpOptions) {
+        self.tcp_options.insert((from, to), options);
+    }
+
+    /// Weight of the (source, destination) flow on `WeightedFair` links. Defaults
+    /// to 1; weights that aren't positive and finite are ignored.
+    pub fn set_flow_weight(&mut self, source: usize, destination: usize, weight: f64) {
+        if weight > 0.0 && weight.is_finite() {
+            self.flow_weights.insert((source, destination), weight);
+        }
+    }
+
+    /// Makes a link's available bandwidth vary over time. Each (threshold, bandwidth)
+    /// entry applies from its threshold until the next one; pass an empty schedule
+    /// to restore the nominal bandwidth.
+    pub fn set_bandwidth_schedule(&mut self, from: usize, to: usize, mut schedule: Vec<(f64, f64)>) {
+        schedule.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.bandwidth_schedule = schedule;
+        }
+    }
+
+    /// Sets the duplex mode on both directions of the `a` <-> `b` pair. Half-duplex
+    /// pairs share a single FIFO backlog (`queue_end_time`).
+    pub fn set_link_duplex(&mut self, a: usize, b: usize, duplex: Duplex) {
+        for link in self.links.iter_mut().filter(|l| (l.from == a && l.to == b) || (l.from == b && l.to == a)) {
+            link.duplex = duplex;
+        }
+    }
+
+    pub fn set_link_ecn(&mut self, from: usize, to: usize, threshold_bytes: Option<usize>) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.ecn_threshold_bytes = threshold_bytes;
+        }
+    }
+
+    pub fn set_link_queue_capacity(&mut self, from: usize, to: usize, capacity_bytes: Option<usize>) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.queue_capacity_bytes = capacity_bytes;
+        }
+    }
+
+    pub fn set_link_red(&mut self, from: usize, to: usize, red: Option<RedConfig>) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.red = red;
+        }
+    }
+
+    pub fn set_link_cost(&mut self, from: usize, to: usize, cost: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.cost = cost;
+        }
+        self.route_cache.clear();
+    }
+
+    pub fn set_link_cost_per_gb(&mut self, from: usize, to: usize, cost_per_gb: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.cost_per_gb = cost_per_gb.max(0.0);
+        }
+    }
+
+    pub fn tag_link(&mut self, from: usize, to: usize, tag: &str) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            if !link.tags.iter().any(|t| t == tag) {
+                link.tags.push(tag.to_string());
+            }
+        }
+    }
+
+    pub fn set_link_jitter(&mut self, from: usize, to: usize, jitter: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.jitter = jitter.max(0.0);
+        }
+    }
+
+    pub fn set_link_loss(&mut self, from: usize, to: usize, loss_rate: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.loss_rate = loss_rate.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Turns WAN deduplication on or off for a link, forgetting what it has seen.
+    pub fn set_link_dedup(&mut self, from: usize, to: usize, enabled: bool) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.dedup = enabled;
+            link.seen_payloads.clear();
+        }
+    }
+
+    pub fn set_link_duplication(&mut self, from: usize, to: usize, probability: f64) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.duplication_probability = probability.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Splits the one-way cost of sending `packet_size` bytes over a link into
+    /// (propagation, serialization) seconds, ignoring queuing.
+    pub fn link_delay_breakdown(&self, from: usize, to: usize, packet_size: usize) -> Option<(f64, f64)> {
+        let link = self.get_link(from, to)?;
+        Some((link.propagation_delay(), link.transmission_time(packet_size)))
+    }
+
+    /// Round-trip propagation time along the lowest-latency path, ignoring queuing.
+    pub fn path_rtt(&self, from: usize, to: usize) -> Option<f64> {
+        Some(self.path_latency(from, to)? * 2.0)
+    }
+
+    /// Mathis et al. steady-state TCP ceiling, `MSS / (RTT * sqrt(loss))`, in
+    /// bits per second over the lowest-latency path's propagation RTT. Zero if
+    /// there's no path; unbounded without loss.
+    pub fn mathis_throughput(&self, from: usize, to: usize, mss: usize, loss: f64) -> f64 {
+        let Some(rtt) = self.path_rtt(from, to) else { return 0.0 };
+        (mss * 8) as f64 / (rtt * loss.sqrt())
+    }
+
+    /// One-way propagation latency along the lowest-latency path.
+    pub fn path_latency(&self, from: usize, to: usize) -> Option<f64> {
+        let path = self.shortest_path(from, to, 0.0f64, |latency, link| latency + link.latency)?;
+        Some(path.windows(2)
+            .filter_map(|hop| self.links.iter().find(|l| l.from == hop[0] && l.to == hop[1]))
+            .map(|l| l.latency)
+            .sum())
+    }
+
+    /// Minimum-latency path found with A*, using light-in-fiber time along the
+    /// great circle to `to` as the heuristic. That never overestimates as long as
+    /// cables are at least as long as the great circle (`path_inefficiency_factor >= 1`).
+    pub fn find_path_astar(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        self.latency_search(from, to, true).0
+    }
+
+    /// Minimum-latency path by plain Dijkstra.
+    pub fn find_path_dijkstra(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        self.latency_search(from, to, false).0
+    }
+
+    /// Nodes expanded finding the minimum-latency path from `from` to `to`, by
+    /// plain Dijkstra and by A*.
+    pub fn search_effort(&self, from: usize, to: usize) -> (usize, usize) {
+        (self.latency_search(from, to, false).1, self.latency_search(from, to, true).1)
+    }
+
+    /// Best-first search on link latency, optionally guided by the geographic
+    /// heuristic. Returns the path and the number of nodes expanded.
+    fn latency_search(&self, from: usize, to: usize, heuristic: bool) -> (Option<Vec<usize>>, usize) {
+        let target = self.location_of(to);
+        let estimate = |node: usize| match (heuristic, target, self.location_of(node)) {
+            (true, Some(target), Some(here)) => here.distance_with(target, &self.physics) / self.physics.speed_in_fiber,
+            _ => 0.0,
+        };
+
+        let mut best: HashMap<usize, f64> = HashMap::from([(from, 0.0)]);
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut settled = HashSet::new();
+
+        loop {
+            let Some(current) = best.iter()
+                .filter(|(node, _)| !settled.contains(*node))
+                .map(|(node, cost)| (*node, cost + estimate(*node)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(node, _)| node)
+            else {
+                return (None, settled.len());
+            };
+            settled.insert(current);
+            if current == to { break; }
+
+            let cost = best[&current];
+            for link in self.links.iter().filter(|l| l.from == current && !settled.contains(&l.to) && l.accepts_traffic(self.current_time)) {
+                let candidate = cost + link.latency;
+                if best.get(&link.to).is_none_or(|c| candidate < *c) {
+                    best.insert(link.to, candidate);
+                    previous.insert(link.to, current);
+                }
+            }
+        }
+
+        let mut path = vec![to];
+        while let Some(prev) = path.last().and_then(|node| previous.get(node)) {
+            path.push(*prev);
+        }
+        path.reverse();
+        (Some(path), settled.len())
+    }
+
+    /// Shortest-path latencies for every ordered pair of distinct, connected nodes.
+    fn all_pairs_latencies(&self) -> Vec<f64> {
+        let ids: Vec<usize> = self.all_nodes().map(|(id, _)| id).collect();
+        ids.iter()
+            .flat_map(|&from| ids.iter().filter(move |&&to| to != from).map(move |&to| (from, to)))
+            .filter_map(|(from, to)| self.path_latency(from, to))
+            .collect()
     }
-}
\ No newline at end of file
+
+    /// Nodes whose total inbound link capacity exceeds their total outbound
+    /// capacity by more than `OVERSUBSCRIPTION_WARN_RATIO`, worst first. Nodes
+    /// with no outbound links are skipped.
+    pub fn check_oversubscription(&self) -> Vec<OversubWarning> {
+        let mut capacity: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+        for link in &self.links {
+            capacity.entry(link.to).or_default().0 += link.bandwidth;
+            capacity.entry(link.from).or_default().1 += link.bandwidth;
+        }
+        let mut warnings: Vec<OversubWarning> = capacity.into_iter()
+            .filter(|(_, (_, outbound))| *outbound > 0.0)
+            .map(|(node, (inbound_bps, outbound_bps))| OversubWarning { node, inbound_bps, outbound_bps, ratio: inbound_bps / outbound_bps })
+            .filter(|w| w.ratio > OVERSUBSCRIPTION_WARN_RATIO)
+            .collect();
+        warnings.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+        warnings
+    }
+
+    /// Largest one-way shortest-path latency between any two connected nodes.
+    pub fn network_diameter(&self) -> f64 {
+        self.all_pairs_latencies().into_iter().fold(0.0, f64::max)
+    }
+
+    /// Mean one-way shortest-path latency over all connected node pairs.
+    pub fn average_path_latency(&self) -> f64 {
+        let latencies = self.all_pairs_latencies();
+        if latencies.is_empty() { return 0.0; }
+        latencies.iter().sum::<f64>() / latencies.len() as f64
+    }
+
+    /// Every server and client id in ascending order; the row and column order
+    /// of `latency_matrix`.
+    pub fn node_order(&self) -> Vec<usize> {
+        let mut nodes: Vec<usize> = self.servers.keys().chain(self.clients.keys()).copied().collect();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    /// One-way shortest-path propagation latency between every pair of nodes,
+    /// indexed by `node_order`. The diagonal is zero and unreachable pairs are
+    /// infinite.
+    pub fn latency_matrix(&self) -> Vec<Vec<f64>> {
+        let nodes = self.node_order();
+        nodes.iter()
+            .map(|&from| nodes.iter()
+                .map(|&to| if from == to { 0.0 } else { self.path_latency(from, to).unwrap_or(f64::INFINITY) })
+                .collect())
+            .collect()
+    }
+
+    /// Throughput ceiling (bps) for a sender that may only have `window_bytes`
+    /// unacknowledged: one window per propagation RTT. Zero if unreachable.
+    pub fn max_window_throughput(&self, from: usize, to: usize, window_bytes: usize) -> f64 {
+        match self.path_rtt(from, to) {
+            Some(rtt) if rtt > 0.0 => window_bytes as f64 * 8.0 / rtt,
+            _ => 0.0,
+        }
+    }
+
+    /// Slowest link bandwidth along the routed path from `from` to `to`.
+    pub fn bottleneck_bandwidth(&self, from: usize, to: usize) -> Option<f64> {
+        let mut node = from;
+        let mut bottleneck = f64::INFINITY;
+        while node != to {
+            let next = self.find_next_hop(node, to)?;
+            let link = self.get_link(node, next)?;
+            bottleneck = bottleneck.min(link.bandwidth);
+            node = next;
+        }
+        Some(bottleneck)
+    }
+
+    /// Starts tracking a freshly sent packet and schedules its first timeout.
+    fn arm_retransmit(&mut self, packet: &DataPacket) {
+        let Some(policy) = self.retransmit else { return };
+        if self.unacked.contains_key(&packet.id) { return; }
+        let Some(rtt) = self.path_rtt(packet.source_id, packet.destination_id) else { return };
+        self.unacked.insert(packet.id, 0);
+        self.push_event(
+            self.current_time + rtt * policy.rto_rtt_multiple,
+            DataPacket { path: Vec::new(), ..packet.clone() },
+            EventType::RetransmitTimeout(packet.source_id),
+        );
+    }
+
+    /// Resends `packet` if it is still undelivered and has retries left.
+    fn handle_retransmit_timeout(&mut self, node_id: usize, packet: DataPacket) {
+        let Some(policy) = self.retransmit else { return };
+        let Some(retries) = self.unacked.get_mut(&packet.id) else { return };
+        if *retries >= policy.max_retries {
+            self.unacked.remove(&packet.id);
+            return;
+        }
+        *retries += 1;
+        let attempt = *retries;
+        let backoff = 2f64.powi(attempt as i32);
+        self.retransmissions += 1;
+        if self.verbose {
+            println!("[{:.4}s] Retransmitting packet (ID {}) from {} (attempt {})",
+                     self.current_time, packet.id, self.get_node_name(node_id), attempt + 1);
+        }
+
+        let rtt = self.path_rtt(packet.source_id, packet.destination_id).unwrap_or(0.0);
+        self.push_event(
+            self.current_time + rtt * policy.rto_rtt_multiple * backoff,
+            packet.clone(),
+            EventType::RetransmitTimeout(node_id),
+        );
+        self.forward_packet(node_id, packet);
+    }
+
+    /// Links along the route the current strategy takes from `from` to `to`,
+    /// or None if there's no loop-free route.
+    fn route_links(&self, from: usize, to: usize) -> Option<Vec<&NetworkLink>> {
+        let mut visited = HashSet::from([from]);
+        let mut node = from;
+        let mut links = Vec::new();
+        while node != to {
+            let next_hop = self.routing.next_hop(self, node, to)?;
+            links.push(self.get_link(node, next_hop)?);
+            if !visited.insert(next_hop) { return None; }
+            node = next_hop;
+        }
+        Some(links)
+    }
+
+    /// How much farther data travels than the crow flies: fiber length along
+    /// the route the current strategy takes, over the great-circle distance
+    /// between the endpoints. A single cable scores `PATH_INEFFICIENCY_FACTOR`;
+    /// detours score higher. Infinite if there's no route.
+    pub fn path_stretch(&self, from: usize, to: usize) -> f64 {
+        let Some(links) = self.route_links(from, to) else { return f64::INFINITY };
+        let direct = self.calculate_distance(from, to);
+        if direct <= 0.0 { return 1.0; }
+        links.iter().map(|l| l.distance).sum::<f64>() / direct
+    }
+
+    /// Smallest MTU along the route the current strategy takes from `from` to
+    /// `to`, i.e. the largest packet that crosses without fragmentation.
+    pub fn path_mtu(&self, from: usize, to: usize) -> Option<usize> {
+        self.route_links(from, to)?.iter().map(|l| l.mtu).min()
+    }
+
+    /// Bandwidth on a link not already taken by measured load or admitted streams.
+    fn residual_bandwidth(&self, link: &NetworkLink) -> f64 {
+        let capacity = link.effective_bandwidth(self.current_time);
+        let reserved = self.stream_reservations.get(&(link.from, link.to)).copied().unwrap_or(0.0);
+        (capacity * (1.0 - self.link_load(link.from, link.to)) - reserved).max(0.0)
+    }
+
+    /// Whether a stream of `bitrate_bps` fits on the route from `from` to `to`:
+    /// every link's residual bandwidth (capacity less its measured load and the
+    /// streams already admitted with `reserve_stream`) must cover it.
+    pub fn admit_stream(&self, from: usize, to: usize, bitrate_bps: f64) -> bool {
+        self.route_links(from, to)
+            .is_some_and(|links| links.iter().all(|l| self.residual_bandwidth(l) >= bitrate_bps))
+    }
+
+    /// Admits the stream like `admit_stream` and, if it fits, reserves its
+    /// bitrate on every link of the route so later streams see less headroom.
+    pub fn reserve_stream(&mut self, from: usize, to: usize, bitrate_bps: f64) -> bool {
+        if !self.admit_stream(from, to, bitrate_bps) { return false; }
+        let hops: Vec<(usize, usize)> = self.route_links(from, to)
+            .map(|links| links.iter().map(|l| (l.from, l.to)).collect())
+            .unwrap_or_default();
+        for hop in hops {
+            *self.stream_reservations.entry(hop).or_insert(0.0) += bitrate_bps;
+        }
+        true
+    }
+
+    /// Path MTU discovery: splits `size_bytes` into `path_mtu`-sized packets at
+    /// the source so nothing is fragmented mid-path. Returns how many packets
+    /// were sent, or 0 if there's no route.
+    pub fn send_packet_pmtud(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType) -> usize {
+        let Some(mtu) = self.path_mtu(from, to) else { return 0 };
+        let count = size_bytes.div_ceil(mtu).max(1);
+        (0..count)
+            .filter(|&index| self.send_packet_ex(from, to, mtu.min(size_bytes - index * mtu), p_type))
+            .count()
+    }
+
+    /// M/M/1 prediction of the mean time a packet waits in the link's queue
+    /// before serialization, for `offered_bps` of Poisson traffic in packets
+    /// averaging `mean_packet_bytes`. `None` if there's no such link.
+    pub fn predicted_queuing_delay(&self, from: usize, to: usize, offered_bps: f64, mean_packet_bytes: usize) -> Option<f64> {
+        let link = self.get_link(from, to)?;
+        let bits = (mean_packet_bytes * 8) as f64;
+        let service_rate = link.effective_bandwidth(self.current_time) / bits;
+        Some(mm1_mean_delay(offered_bps / bits, service_rate) - 1.0 / service_rate)
+    }
+
+    pub fn set_link_mtu(&mut self, from: usize, to: usize, mtu: usize) {
+        if let Some(link) = self.links.iter_mut().find(|l| l.from == from && l.to == to) {
+            link.mtu = mtu;
+        }
+    }
+
+    /// Spreads new flows at `node` across several interchangeable uplinks in
+    /// proportion to `weights` (next hop, weight). Whenever routing would send a
+    /// flow out one of these uplinks, its first packet is assigned one by smooth
+    /// weighted round-robin and the rest of the flow sticks to it. Unlike ECMP
+    /// the weights needn't be equal. An empty list removes the configuration.
+    pub fn set_uplink_weights(&mut self, node: usize, weights: Vec<(usize, u32)>) {
+        self.uplink_credit.retain(|(n, _), _| *n != node);
+        self.flow_uplinks.retain(|(n, _, _), _| *n != node);
+        let weights: Vec<(usize, u32)> = weights.into_iter().filter(|(_, weight)| *weight > 0).collect();
+        if weights.is_empty() {
+            self.uplink_weights.remove(&node);
+        } else {
+            self.uplink_weights.insert(node, weights);
+        }
+    }
+
+    /// The uplink a packet routed to `routed` should actually take, per `set_uplink_weights`.
+    fn weighted_uplink(&mut self, node: usize, packet: &DataPacket, routed: usize) -> usize {
+        let Some(weights) = self.uplink_weights.get(&node) else { return routed };
+        if !weights.iter().any(|(uplink, _)| *uplink == routed) { return routed; }
+
+        let flow = (node, packet.source_id, packet.destination_id);
+        let now = self.current_time;
+        let usable = |uplink: usize| self.links.iter().any(|l| l.from == node && l.to == uplink && l.accepts_traffic(now));
+        if let Some(&pinned) = self.flow_uplinks.get(&flow) {
+            if usable(pinned) { return pinned; }
+        }
+
+        let candidates: Vec<(usize, u32)> = weights.iter().copied().filter(|(uplink, _)| usable(*uplink)).collect();
+        let total: i64 = candidates.iter().map(|(_, weight)| *weight as i64).sum();
+        let mut chosen = None;
+        for (uplink, weight) in &candidates {
+            let credit = self.uplink_credit.entry((node, *uplink)).or_insert(0);
+            *credit += *weight as i64;
+            if chosen.is_none_or(|(_, best)| *credit > best) {
+                chosen = Some((*uplink, *credit));
+            }
+        }
+        let Some((uplink, _)) = chosen else { return routed };
+        *self.uplink_credit.entry((node, uplink)).or_insert(0) -= total;
+        self.flow_uplinks.insert(flow, uplink);
+        uplink
+    }
+
+    /// Hands out monotonically increasing packet ids, so packets created in the
+    /// same burst never share an id.
+    pub fn allocate_packet_id(&mut self) -> usize {
+        let id = self.next_packet_id;
+        self.next_packet_id += 1;
+        id
+    }
+
+    fn new_packet(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType, created_at: f64) -> DataPacket {
+        let next = self.next_sequence.entry((from, to)).or_insert(0);
+        let sequence = *next;
+        *next += 1;
+        DataPacket { id: self.allocate_packet_id(), sequence, ..Self::unnumbered_packet(from, to, size_bytes, p_type, created_at) }
+    }
+
+    /// A packet that uses up no id or sequence number (both are 0), for probes
+    /// that never enter the network.
+    fn unnumbered_packet(from: usize, to: usize, size_bytes: usize, p_type: PacketType, created_at: f64) -> DataPacket {
+        DataPacket {
+            id: 0,
+            source_id: from,
+            destination_id: to,
+            size_bytes,
+            created_at,
+            packet_type: p_type,
+            content_id: 0,
+            payload_hash: 0,
+            fragment: None,
+            path: Vec::new(),
+            ecn_marked: false,
+            source_route: None,
+            multicast_routes: Vec::new(),
+            sequence: 0,
+            flow_id: None,
+            queuing_delay: 0.0,
+        }
+    }
+
+    /// Sends a packet from `from` now. Returns false if it was dropped at the
+    /// source because `to` is unreachable (recorded as `DropReason::NoRoute`)
+    /// or the event queue is full (`DropReason::AdmissionControl`).
+    pub fn send_packet_ex(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType) -> bool {
+        let packet = self.new_packet(from, to, size_bytes, p_type, self.current_time);
+        let Some(packet) = self.admit(packet) else { return false };
+        self.forward_packet(from, packet)
+    }
+
+    /// Sends `size_bytes` of content `content_id` from `from` now, like
+    /// `send_packet_ex`. The packet's `payload_hash` lets deduplicating links
+    /// recognise a repeat, and a `CdnRequest` is served from edge caches by
+    /// `content_id`.
+    pub fn send_content(&mut self, from: usize, to: usize, content_id: u64, size_bytes: usize, p_type: PacketType) -> bool {
+        let packet = DataPacket {
+            content_id,
+            payload_hash: payload_hash(content_id, size_bytes),
+            ..self.new_packet(from, to, size_bytes, p_type, self.current_time)
+        };
+        let Some(packet) = self.admit(packet) else { return false };
+        self.forward_packet(from, packet)
+    }
+
+    /// Writes `size_bytes` to the `from` -> `to` stream under its `TcpOptions`.
+    /// Each segment goes out as a `Standard` packet and is acknowledged with
+    /// a `TcpAck`.
+    pub fn send_segment(&mut self, from: usize, to: usize, size_bytes: usize) {
+        let nagle = self.tcp_options.get(&(from, to)).is_some_and(|o| o.nagle);
+        let stream = self.tcp_streams.entry((from, to)).or_default();
+        if nagle && stream.unacked_segments > 0 && stream.nagle_buffer + size_bytes < TCP_MSS {
+            stream.nagle_buffer += size_bytes;
+            return;
+        }
+        let size_bytes = size_bytes + std::mem::take(&mut stream.nagle_buffer);
+        self.send_stream_data(from, to, size_bytes);
+    }
+
+    fn send_stream_data(&mut self, from: usize, to: usize, size_bytes: usize) {
+        if let Some(stream) = self.tcp_streams.get_mut(&(from, to)) {
+            stream.unacked_segments += 1;
+        }
+        // The segment carries any ACK owed on the reverse stream
+        if let Some(reverse) = self.tcp_streams.get_mut(&(to, from)) {
+            reverse.received_since_ack = 0;
+            reverse.ack_deadline = None;
+        }
+        self.send_packet_ex(from, to, size_bytes, PacketType::Standard);
+    }
+
+    /// Receiver side of a stream segment delivered at `node_id`.
+    fn receive_segment(&mut self, node_id: usize, packet: &DataPacket) {
+        let from = packet.source_id;
+        // Data from the peer also acknowledges our own stream to it
+        if self.tcp_streams.get(&(node_id, from)).is_some_and(|s| s.unacked_segments > 0) {
+            self.stream_acked(node_id, from);
+        }
+        let delayed_ack = self.tcp_options.get(&(from, node_id)).and_then(|o| o.delayed_ack);
+        let Some(stream) = self.tcp_streams.get_mut(&(from, node_id)) else { return };
+        stream.received_since_ack += 1;
+        match delayed_ack {
+            Some(timeout) if stream.received_since_ack < 2 => {
+                let deadline = self.current_time + timeout;
+                stream.ack_deadline = Some(deadline);
+                self.push_event(deadline, packet.clone(), EventType::DelayedAck(node_id));
+            }
+            _ => self.send_stream_ack(node_id, from),
+        }
+    }
+
+    fn send_stream_ack(&mut self, node_id: usize, to: usize) {
+        if let Some(stream) = self.tcp_streams.get_mut(&(to, node_id)) {
+            stream.received_since_ack = 0;
+            stream.ack_deadline = None;
+        }
+        self.send_packet_ex(node_id, to, 64, PacketType::TcpAck);
+    }
+
+    /// Everything `sender` had outstanding to `receiver` is acknowledged, which
+    /// releases whatever Nagle was holding.
+    fn stream_acked(&mut self, sender: usize, receiver: usize) {
+        let Some(stream) = self.tcp_streams.get_mut(&(sender, receiver)) else { return };
+        stream.unacked_segments = 0;
+        let held = std::mem::take(&mut stream.nagle_buffer);
+        if held > 0 {
+            self.send_stream_data(sender, receiver, held);
+        }
+    }
+
+    /// Passes `packet` through unless the event queue is at `max_events`, in
+    /// which case it's recorded as an admission-control drop.
+    fn admit(&mut self, packet: DataPacket) -> Option<DataPacket> {
+        if self.max_events.is_some_and(|cap| self.event_queue.len() >= cap) {
+            self.record_drop(packet, DropReason::AdmissionControl);
+            return None;
+        }
+        Some(packet)
+    }
+
+    /// Sends to whichever member of an anycast `group` has the lowest physical
+    /// latency floor from `from`. Returns the chosen replica, or `None` if no
+    /// member is reachable.
+    pub fn send_anycast(&mut self, from: usize, group: &[usize], size_bytes: usize, p_type: PacketType) -> Option<usize> {
+        let replica = group.iter()
+            .map(|&member| (member, self.theoretical_min_latency(from, member)))
+            .filter(|(_, floor)| floor.is_finite())
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .map(|(member, _)| member)?;
+        self.send_packet_ex(from, replica, size_bytes, p_type).then_some(replica)
+    }
+
+    /// Delivers one packet to every member of `group` over a shortest-path tree:
+    /// shared links carry it once and copies are made only where branches split.
+    /// Returns the number of tree links, or 0 if nothing was sent (no reachable
+    /// members, or the packet exceeds a tree link's MTU since multicast copies
+    /// are never fragmented).
+    pub fn send_multicast(&mut self, from: usize, group: &[usize], size_bytes: usize, p_type: PacketType) -> usize {
+        let routes: Vec<Vec<usize>> = group.iter()
+            .filter(|&&member| member != from)
+            .filter_map(|&member| self.shortest_path(from, member, 0.0f64, |latency, link| latency + link.latency))
+            .collect();
+        let tree: HashSet<(usize, usize)> = routes.iter()
+            .flat_map(|route| route.windows(2).map(|hop| (hop[0], hop[1])))
+            .collect();
+        let fits = tree.iter()
+            .all(|(a, b)| self.links.iter().any(|l| l.from == *a && l.to == *b && l.mtu >= size_bytes));
+        let (Some(first), true) = (routes.first(), fits) else { return 0 };
+
+        let packet = DataPacket {
+            multicast_routes: routes.clone(),
+            ..self.new_packet(from, *first.last().unwrap_or(&from), size_bytes, p_type, self.current_time)
+        };
+        self.forward_packet(from, packet);
+        tree.len()
+    }
+
+    /// Delivers a copy at `node_id` if it is a group member, then keeps
+    /// forwarding for members further down the tree.
+    fn handle_multicast_arrival(&mut self, node_id: usize, mut packet: DataPacket) {
+        let (here, onward): (Vec<_>, Vec<_>) = std::mem::take(&mut packet.multicast_routes)
+            .into_iter()
+            .partition(|route| route.last() == Some(&node_id));
+
+        if !here.is_empty() {
+            let mut delivered = DataPacket { destination_id: node_id, ..packet.clone() };
+            delivered.path.push(node_id);
+            let latency = self.current_time - delivered.created_at;
+            if self.record_events {
+                let event = self.new_event(
+                    self.current_time,
+                    delivered.clone(),
+                    EventType::PacketArrival(node_id),
+                );
+                self.event_log.push(event);
+            }
+            if self.verbose {
+                println!("[{:.4}s] Multicast {:?} packet (ID {}) arrived at {} | Latency: {:.2} ms",
+                         self.current_time, delivered.packet_type, delivered.id, self.get_node_name(node_id), latency * 1000.0);
+            }
+            self.completed_packets.push((delivered, latency));
+        }
+
+        if !onward.is_empty() {
+            let delay = self.service_time(node_id);
+            self.push_event(
+                self.current_time + delay,
+                DataPacket { multicast_routes: onward, ..packet },
+                EventType::PacketTransmissionComplete(node_id),
+            );
+        }
+    }
+
+    /// Sends a packet strictly along `path` (source first, destination last),
+    /// ignoring the routing algorithm. The route is checked hop by hop as the
+    /// packet travels; a missing link drops it with `InvalidSourceRoute`.
+    pub fn send_packet_routed(&mut self, path: &[usize], size_bytes: usize, p_type: PacketType) {
+        let (Some(&from), Some(&to)) = (path.first(), path.last()) else { return };
+        let packet = DataPacket {
+            source_route: Some(path.to_vec()),
+            ..self.new_packet(from, to, size_bytes, p_type, self.current_time)
+        };
+        self.forward_packet(from, packet);
+    }
+
+    /// Starts a server with `content_ids` already cached, as if warmed up.
+    /// Only edges serve from cache, so this has no effect on other roles.
+    pub fn preload_cache(&mut self, server_id: usize, content_ids: &[u64]) {
+        if let Some(server) = self.servers.get_mut(&server_id) {
+            server.cache.extend(content_ids);
+        }
+    }
+
+    /// Requests `content_id` from a server. Edge servers answer from cache or
+    /// fetch from their origin first; origins always have the content.
+    /// Returns the request's packet id, for `ttfb`.
+    pub fn send_cdn_request(&mut self, client: usize, server: usize, content_id: u64) -> usize {
+        let packet = DataPacket {
+            content_id,
+            ..self.new_packet(client, server, 512, PacketType::CdnRequest, self.current_time)
+        };
+        let id = packet.id;
+        self.open_cdn_requests.entry((client, server, content_id)).or_default().push_back((id, self.current_time));
+        self.forward_packet(client, packet);
+        id
+    }
+
+    /// Fetches `num_requests` distinct content ids from `server` twice, from the
+    /// same starting state: one request at a time, then all at once. The
+    /// simulation is restored afterwards, so neither run's cache fills or
+    /// queued traffic leak into the other or into later work.
+    pub fn compare_chatty_vs_batched(&mut self, client: usize, server: usize, num_requests: usize) -> ChattyBatchedReport {
+        let before = self.snapshot();
+        let start = self.current_time;
+
+        let mut chatty_time = 0.0;
+        for content_id in 0..num_requests as u64 {
+            let request = self.send_cdn_request(client, server, content_id);
+            if !self.run_until_answered(&[request]) {
+                chatty_time = f64::INFINITY;
+                break;
+            }
+            chatty_time = self.current_time - start;
+        }
+        self.restore(&before);
+
+        let requests: Vec<usize> = (0..num_requests as u64)
+            .map(|content_id| self.send_cdn_request(client, server, content_id))
+            .collect();
+        let batched_time = if self.run_until_answered(&requests) { self.current_time - start } else { f64::INFINITY };
+        self.restore(&before);
+
+        ChattyBatchedReport { requests: num_requests, chatty_time, batched_time }
+    }
+
+    /// Processes events until every request in `requests` has its response,
+    /// stopping on the event that completes the last. False if the queue runs
+    /// dry first.
+    fn run_until_answered(&mut self, requests: &[usize]) -> bool {
+        while !requests.iter().all(|id| self.ttfb_by_request.contains_key(id)) {
+            let Some(next) = self.event_queue.peek().map(|e| e.time) else { return false };
+            self.run_simulation(next);
+        }
+        true
+    }
+
+    /// Time to first byte: from the request's creation to the first bit of its
+    /// response reaching the requester. `None` until the response arrives.
+    pub fn ttfb(&self, request_packet_id: usize) -> Option<f64> {
+        self.ttfb_by_request.get(&request_packet_id).copied()
+    }
+
+    /// (forward, return) node paths of an answered CDN request, each starting
+    /// at its sender. `None` until the response arrives.
+    pub fn round_trip_paths(&self, request_packet_id: usize) -> Option<(&[usize], &[usize])> {
+        let (forward, back) = self.request_paths.get(&request_packet_id)?;
+        Some((forward, back.as_deref()?))
+    }
+
+    /// Whether an answered CDN request's response retraced its path in
+    /// reverse. False if the paths differ or the response hasn't arrived.
+    pub fn is_path_symmetric(&self, request_packet_id: usize) -> bool {
+        self.round_trip_paths(request_packet_id)
+            .is_some_and(|(forward, back)| forward.iter().rev().eq(back.iter()))
+    }
+
+    /// Pairs a delivered response with the oldest open request to its server for that content.
+    fn record_ttfb(&mut self, node_id: usize, response: &DataPacket) {
+        let Some(open) = self.open_cdn_requests.get_mut(&(node_id, response.source_id, response.content_id)) else { return };
+        let Some((request_id, sent_at)) = open.pop_front() else { return };
+        if let Some((_, back)) = self.request_paths.get_mut(&request_id) {
+            *back = Some(response.path.clone());
+        }
+        // Arrival is of the last bit; the first came one serialization time earlier
+        let serialization = response.path.iter().rev().nth(1)
+            .and_then(|&previous| self.get_link(previous, node_id))
+            .map_or(0.0, |link| link.transmission_time_at(response.size_bytes, self.current_time));
+        self.ttfb_by_request.insert(request_id, self.current_time - serialization - sent_at);
+    }
+
+    /// Looks up `server` at `resolver`, then opens a TCP connection to it once
+    /// the answer arrives. The connection is recorded with its `dns_time`.
+    pub fn resolve_then_connect(&mut self, client: usize, resolver: usize, server: usize) {
+        self.pending_lookups.insert((client, server), self.current_time);
+        let query = DataPacket {
+            content_id: server as u64,
+            ..self.new_packet(client, resolver, 80, PacketType::DnsQuery, self.current_time)
+        };
+        self.forward_packet(client, query);
+    }
+
+    fn complete_lookup(&mut self, client: usize, answer: &DataPacket) {
+        let server = answer.content_id as usize;
+        let Some(queried_at) = self.pending_lookups.remove(&(client, server)) else { return };
+        self.dns_times.insert((client, server), self.current_time - queried_at);
+        self.send_packet_ex(client, server, 64, PacketType::TcpSyn);
+    }
+
+    fn send_cdn_response(&mut self, server: usize, client: usize, content_id: u64) {
+        // CDN server responds with the cached data (1KB for demo)
+        let packet = DataPacket {
+            content_id,
+            payload_hash: payload_hash(content_id, 1024),
+            ..self.new_packet(server, client, 1024, PacketType::CdnResponse, self.current_time)
+        };
+        self.forward_packet(server, packet);
+    }
+
+    /// Walks a single packet from `from` to `to` starting now, against the links'
+    /// current backlogs but without adding it to them, so the breakdown reflects
+    /// the packet in isolation. Like a sent packet it is split at each link's
+    /// MTU, and fragments move on independently; a hop is then timed from the
+    /// arrival of the last fragment, whose wait includes its siblings ahead of
+    /// it. Uses up no packet id. Returns `None` if there is no route.
+    pub fn trace_packet(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType) -> Option<PacketTrace> {
+        let packet = Self::unnumbered_packet(from, to, size_bytes, p_type, self.current_time);
+        let weight = self.flow_weights.get(&(from, to)).copied().unwrap_or(1.0);
+        let mut hops = Vec::new();
+        let mut node = from;
+        // Arrival time and size of each fragment at `node`, in arrival order
+        let mut fragments = vec![(self.current_time, size_bytes)];
+
+        while node != to {
+            let next_hop = self.route_next_hop(node, to)?;
+            let mut link = self.get_link(node, next_hop)?.clone();
+            let processing = if node == from { 0.0 } else { self.servers.get(&node).map_or(0.0, |s| s.processing_delay) };
+            let mut arrivals = Vec::new();
+            let mut last = (0.0, 0.0, 0.0);
+            for (arrived, size) in fragments {
+                let pieces = if size > link.mtu {
+                    (0..size).step_by(link.mtu).map(|offset| link.mtu.min(size - offset)).collect()
+                } else {
+                    vec![size]
+                };
+                for piece in pieces {
+                    let ready = arrived + processing;
+                    let (start, finish) = link.reserve(ready, &DataPacket { size_bytes: piece, ..packet.clone() }, weight);
+                    arrivals.push((finish + link.latency, piece));
+                    last = (ready, start, finish);
+                }
+            }
+            let (ready, start, finish) = last;
+            hops.push(HopTrace {
+                from: node,
+                to: next_hop,
+                processing,
+                queuing: start - ready,
+                transmission: finish - start,
+                propagation: link.latency,
+            });
+            fragments = arrivals;
+            node = next_hop;
+        }
+
+        let total_latency = hops.iter().map(HopTrace::total).sum();
+        Some(PacketTrace { hops, total_latency })
+    }
+
+    /// Store-and-forward (DTN) delivery: the bundle is held at each hop until the
+    /// next link's contact window can fit the whole transmission. The route is the
+    /// one with the earliest arrival given the schedule, not the fewest hops.
+    /// Links are assumed otherwise idle. `None` if no sequence of contacts gets
+    /// the bundle to `to`.
+    pub fn send_bundle(&self, from: usize, to: usize, size_bytes: usize, contact_schedule: &ContactSchedule) -> Option<BundleDelivery> {
+        let processing_at = |node: usize| if node == from { 0.0 } else { self.servers.get(&node).map_or(0.0, |s| s.processing_delay) };
+        let departure = |arrived: f64, link: &NetworkLink| {
+            let ready = arrived + processing_at(link.from);
+            let transmission = link.transmission_time_at(size_bytes, ready);
+            contact_schedule.next_departure(link.from, link.to, ready, transmission)
+        };
+
+        let path = self.shortest_path(from, to, self.current_time, |arrived, link| {
+            departure(arrived, link).map_or(f64::INFINITY, |depart| {
+                depart + link.transmission_time_at(size_bytes, depart) + link.latency
+            })
+        })?;
+
+        let mut custody = Vec::new();
+        let mut time = self.current_time;
+        for hop in path.windows(2) {
+            let link = self.get_link(hop[0], hop[1])?;
+            let depart = departure(time, link)?;
+            custody.push((hop[0], depart - time - processing_at(hop[0])));
+            time = depart + link.transmission_time_at(size_bytes, depart) + link.latency;
+        }
+
+        Some(BundleDelivery { path, custody, delivered_at: time })
+    }
+
+    /// Schedules a packet to be created at `source` at absolute time `at`.
+    /// Returns false if admission control refused it.
+    fn schedule_packet(&mut self, from: usize, to: usize, size_bytes: usize, p_type: PacketType, at: f64) -> bool {
+        let packet = self.new_packet(from, to, size_bytes, p_type, at);
+        let Some(packet) = self.admit(packet) else { return false };
+        self.push_event(at, packet, EventType::ScheduledSend(from));
+        true
+    }
+
+    fn complete_handshake(&mut self, client: usize, server: usize) {
+        let Some(syn_sent_at) = self.pending_handshakes.remove(&(client, server)) else { return };
+        let connection = ConnectionEstablished {
+            client,
+            server,
+            syn_sent_at,
+            established_at: self.current_time,
+            setup_time: self.current_time - syn_sent_at,
+            dns_time: self.dns_times.remove(&(client, server)),
+        };
+        if self.verbose {
+            println!("[{:.4}s] Connection established {} -> {} | Setup: {:.2} ms",
+                     self.current_time, self.get_node_name(client), self.get_node_name(server), connection.setup_time * 1000.0);
+        }
+        self.connections.push(connection);
+    }
+
+    fn serve_cdn_request(&mut self, node_id: usize, request: &DataPacket) {
+        self.request_paths.insert(request.id, (request.path.clone(), None));
+        let content_id = request.content_id;
+        let Some(server) = self.servers.get(&node_id) else { return };
+        let (role, upstream, cached) = (server.role, server.origin, server.cache.contains(&content_id));
+
+        let origin = match (role, upstream) {
+            (ServerRole::Origin, _) | (_, None) => None,
+            (ServerRole::Edge, Some(_)) if cached => {
+                // [Sepedi]: Ge resepi entle ya CDN e fihla, re araba kapee-pee
+                self.cache_hits += 1;
+                None
+            }
+            (_, upstream) => upstream,
+        };
+        let Some(origin) = origin else {
+            self.send_cdn_response(node_id, request.source_id, content_id);
+            return;
+        };
+
+        if role == ServerRole::Edge {
+            self.cache_misses += 1;
+        }
+        if self.verbose {
+            println!(
+                "[{:.4}s] {} at {} for content {} -> fetching from {}",
+                self.current_time, if role == ServerRole::Edge { "Cache MISS" } else { "Relaying" },
+                self.get_node_name(node_id), content_id, self.get_node_name(origin)
+            );
+        }
+        let waiting = self.pending_fetches.entry((node_id, content_id)).or_default();
+        waiting.push(request.source_id);
+        if waiting.len() == 1 {
+            self.send_cdn_request(node_id, origin, content_id);
+        }
+    }
+
+    fn complete_origin_fetch(&mut self, node_id: usize, response: &DataPacket) {
+        let Some(waiting) = self.pending_fetches.remove(&(node_id, response.content_id)) else { return };
+        if let Some(server) = self.servers.get_mut(&node_id).filter(|s| s.role == ServerRole::Edge) {
+            server.cache.insert(response.content_id);
+        }
+        for client in waiting {
+            self.send_cdn_response(node_id, client, response.content_id);
+        }
+    }
+
+    /// Replays a packet trace as `Standard` traffic, each record's time taken as
+    /// an offset from now. Records naming unknown nodes are skipped. Returns the
+    /// number of packets scheduled.
+    pub fn inject_trace(&mut self, trace: &[TraceRecord]) -> usize {
+        let start = self.current_time;
+        let mut injected = 0;
+        for &(offset, src, dst, size_bytes) in trace {
+            if self.location_of(src).is_none() || self.location_of(dst).is_none() { continue; }
+            if self.schedule_packet(src, dst, size_bytes, PacketType::Standard, start + offset) {
+                injected += 1;
+            }
+        }
+        injected
+    }
+
+    /// Schedules a batch of (from, to, size_bytes, type, offset) sends, each created
+    /// `offset` seconds after the current simulation time.
+    pub fn send_batch(&mut self, specs: &[SendSpec]) {
+        for &(from, to, size_bytes, p_type, offset) in specs {
+            self.schedule_packet(from, to, size_bytes, p_type, self.current_time + offset);
+        }
+    }
+
+    /// Sends `total_bytes` from `from` to `to` now as one flow of packets of at
+    /// most `packet_size` bytes, tagged with `flow_id`. Returns the number of
+    /// packets that left the source.
+    pub fn send_flow(&mut self, flow_id: u64, from: usize, to: usize, total_bytes: usize, packet_size: usize) -> usize {
+        let count = total_bytes.div_ceil(packet_size).max(1);
+        *self.flow_packets.entry(flow_id).or_insert(0) += count;
+        let mut sent = 0;
+        for index in 0..count {
+            let size_bytes = packet_size.min(total_bytes - index * packet_size);
+            let packet = DataPacket {
+                flow_id: Some(flow_id),
+                ..self.new_packet(from, to, size_bytes, PacketType::Standard, self.current_time)
+            };
+            let Some(packet) = self.admit(packet) else { continue };
+            if self.forward_packet(from, packet) {
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    /// Generates Poisson arrivals (exponential inter-arrival times) from `from` to
+    /// `to` at `rate_pps` for `duration` seconds, with sizes drawn from `sizes`.
+    /// Returns the number of packets scheduled.
+    pub fn generate_poisson_traffic(
+        &mut self,
+        from: usize,
+        to: usize,
+        rate_pps: f64,
+        duration: f64,
+        sizes: &PacketSizeDistribution,
+    ) -> usize {
+        let end_time = self.current_time + duration;
+        let mut time = self.current_time;
+        let mut scheduled = 0;
+
+        loop {
+            time += self.traffic_rng.exponential(1.0 / rate_pps);
+            if time > end_time { break; }
+            let size = sizes.sample(&mut self.traffic_rng);
+            if self.schedule_packet(from, to, size, PacketType::Standard, time) {
+                scheduled += 1;
+            }
+        }
+        scheduled
+    }
+
+    /// Latency-vs-load curve: for each offered load in `rates` (bps), a `reset`
+    /// clone of this simulation (same topology, settings and seed, from time 0)
+    /// gets `LOAD_SWEEP_DURATION` seconds of Poisson traffic in MTU-sized
+    /// packets from `src` to `dst` and runs until it drains. Returns (offered
+    /// load, mean latency) pairs; the latency is infinite if nothing arrived.
+    /// This simulation is left untouched.
+    pub fn load_sweep(&self, src: usize, dst: usize, rates: &[f64]) -> Vec<(f64, f64)> {
+        rates.iter()
+            .map(|&rate| {
+                let mut copy = self.clone();
+                copy.reset();
+                copy.verbose = false;
+
+                let rate_pps = rate / (DEFAULT_MTU * 8) as f64;
+                copy.generate_poisson_traffic(src, dst, rate_pps, LOAD_SWEEP_DURATION, &PacketSizeDistribution::Fixed(DEFAULT_MTU));
+                copy.run_simulation(f64::INFINITY);
+                let latencies: Vec<f64> = copy.completed_for_flow(src, dst)
+                    .map(|(_, latency)| *latency)
+                    .collect();
+                let mean = if latencies.is_empty() { f64::INFINITY } else { latencies.iter().sum::<f64>() / latencies.len() as f64 };
+                (rate, mean)
+            })
+            .collect()
+    }
+
+    /// Processing time for one packet at `node_id`: a fresh draw from the
+    /// server's service time distribution, else its flat `processing_delay`.
+    fn service_time(&mut self, node_id: usize) -> f64 {
+        match self.servers.get(&node_id) {
+            Some(Server { service_time_distribution: Some(distribution), .. }) => distribution.sample(&mut self.rng),
+            Some(server) => server.processing_delay,
+            None => 0.0,
+        }
+    }
+
+    /// Stamps an event with the next insertion sequence number.
+    fn new_event(&mut self, time: f64, packet: DataPacket, event_type: EventType) -> Event {
+        let sequence = self.next_event_sequence;
+        self.next_event_sequence += 1;
+        Event { time, packet, event_type, sequence }
+    }
+
+    /// Queues an event unless `max_events` refuses it. Returns whether it was queued.
+    fn push_event(&mut self, time: f64, packet: DataPacket, event_type: EventType) -> bool {
+        if self.max_events.is_some_and(|cap| self.event_queue.len() >= cap) {
+            if !matches!(event_type, EventType::RetransmitTimeout(_) | EventType::DelayedAck(_)) {
+                self.record_drop(packet, DropReason::AdmissionControl);
+            }
+            return false;
+        }
+        let event = self.new_event(time, packet, event_type);
+        self.event_queue.push(event);
+        true
+    }
+
+    fn record_drop(&mut self, packet: DataPacket, reason: DropReason) {
+        if self.record_events {
+            let node = packet.path.last().copied().unwrap_or(packet.source_id);
+            let event = self.new_event(self.current_time, packet.clone(), EventType::Dropped(node, reason));
+            self.event_log.push(event);
+        }
+        // A spare copy of something already delivered isn't a lost packet
+        if self.received_sequences.contains(&(packet.source_id, packet.destination_id, packet.sequence)) { return; }
+        self.dropped_ids.insert(packet.id);
+        self.dropped_packets.push((packet, reason));
+    }
+
+    /// Schedules `packet` onto the link from `node_id` towards its next hop,
+    /// splitting it into MTU-sized fragments if it does not fit. Returns false
+    /// (after recording the drop) if the packet has nowhere to go.
+    fn forward_packet(&mut self, node_id: usize, mut packet: DataPacket) -> bool {
+        if self.record_events && packet.path.is_empty() {
+            let event = self.new_event(self.current_time, packet.clone(), EventType::ScheduledSend(node_id));
+            self.event_log.push(event);
+        }
+        if !packet.multicast_routes.is_empty() {
+            return self.forward_multicast(node_id, packet);
+        }
+        packet.path.push(node_id);
+        if node_id == packet.destination_id {
+            // Loopback: delivered on the spot without touching a link
+            if self.push_event(self.current_time, packet, EventType::PacketArrival(node_id)) {
+                self.in_flight += 1;
+            }
+            self.peak_in_flight = self.peak_in_flight.max(self.in_flight);
+            return true;
+        }
+        // `path` holds every node visited so far, so one more than the hops taken
+        if self.hop_limit.is_some_and(|limit| packet.path.len() > limit) {
+            self.record_drop(packet, DropReason::HopLimitExceeded);
+            return false;
+        }
+        let next_hop = match &packet.source_route {
+            Some(route) => {
+                let next = route.get(packet.path.len()).copied()
+                    .filter(|next| self.links.iter().any(|l| l.from == node_id && l.to == *next));
+                let Some(next) = next else {
+                    self.record_drop(packet, DropReason::InvalidSourceRoute);
+                    return false;
+                };
+                next
+            }
+            None => {
+                let Some(next) = self.route_next_hop(node_id, packet.destination_id) else {
+                    if self.verbose {
+                        println!("[{:.4}s] No route from {} to {}, packet (ID {}) dropped",
+                                 self.current_time, self.get_node_name(node_id), self.get_node_name(packet.destination_id), packet.id);
+                    }
+                    self.record_drop(packet, DropReason::NoRoute);
+                    return false;
+                };
+                self.weighted_uplink(node_id, &packet, next)
+            }
+        };
+        if node_id == packet.source_id && packet.path.len() == 1 && packet.fragment.is_none() {
+            self.arm_retransmit(&packet);
+        }
+        self.transmit(node_id, next_hop, packet)
+    }
+
+    /// Replicates a multicast packet once per distinct next hop in its tree.
+    fn forward_multicast(&mut self, node_id: usize, mut packet: DataPacket) -> bool {
+        packet.path.push(node_id);
+        let mut branches: BTreeMap<usize, Vec<Vec<usize>>> = BTreeMap::new();
+        for route in std::mem::take(&mut packet.multicast_routes) {
+            let next = route.iter().position(|n| *n == node_id).and_then(|pos| route.get(pos + 1)).copied();
+            if let Some(next) = next {
+                branches.entry(next).or_default().push(route);
+            }
+        }
+        if branches.is_empty() { return false; }
+
+        for (next_hop, routes) in branches {
+            let copy = DataPacket { multicast_routes: routes, ..packet.clone() };
+            self.transmit(node_id, next_hop, copy);
+        }
+        true
+    }
+
+    /// Puts `packet` on the `node_id` -> `next_hop` link.
+    fn transmit(&mut self, node_id: usize, next_hop: usize, packet: DataPacket) -> bool {
+        let current_time = self.current_time;
+        let Some(index) = self.links.iter().position(|l| l.from == node_id && l.to == next_hop) else { return false };
+        if self.unconverged_failures.contains_key(&(node_id, next_hop)) {
+            // Routing hasn't converged yet: the stale next hop is a dead link
+            self.record_drop(packet, DropReason::LinkDown);
+            return true;
+        }
+        let reverse = self.links.iter().position(|l| l.from == next_hop && l.to == node_id)
+            .filter(|_| self.links[index].duplex == Duplex::Half);
+        if let Some(reverse) = reverse {
+            // Wait for whatever the other direction already has on the shared channel
+            self.links[index].queue_end_time = self.links[index].queue_end_time.max(self.links[reverse].queue_end_time);
+        }
+        let mtu = self.links[index].mtu;
+
+        let fragments = if packet.size_bytes > mtu {
+            // Re-fragmenting a fragment keeps offsets relative to the original
+            let base = match packet.fragment {
+                Some(offset) => offset,
+                None => {
+                    // A resend fills in the same entry, so fragments of any copy count
+                    self.reassembly.entry(packet.id)
+                        .or_insert_with(|| Reassembly { packet: packet.clone(), received: HashMap::new() });
+                    0
+                }
+            };
+
+            (0..packet.size_bytes.div_ceil(mtu))
+                .map(|index| DataPacket {
+                    size_bytes: mtu.min(packet.size_bytes - index * mtu),
+                    fragment: Some(base + index * mtu),
+                    ..packet.clone()
+                })
+                .collect()
+        } else {
+            vec![packet]
+        };
+
+        for mut fragment in fragments {
+            let link = &mut self.links[index];
+            let backlog = link.backlog_bytes(current_time);
+            if link.queue_capacity_bytes.is_some_and(|capacity| backlog + fragment.size_bytes > capacity) {
+                self.record_drop(fragment, DropReason::QueueFull);
+                continue;
+            }
+            if let Some(red) = link.red {
+                if self.rng.next_f64() < red.drop_probability(backlog) {
+                    self.record_drop(fragment, DropReason::RedEarly);
+                    continue;
+                }
+            }
+            if link.ecn_threshold_bytes.is_some_and(|threshold| backlog >= threshold) {
+                fragment.ecn_marked = true;
+            }
+            let weight = self.flow_weights.get(&(fragment.source_id, fragment.destination_id)).copied().unwrap_or(1.0);
+            let wire_size = link.wire_size(&fragment);
+            let (start_time, finish_time, displaced) = if wire_size < fragment.size_bytes {
+                link.book(current_time, &DataPacket { size_bytes: wire_size, ..fragment.clone() }, weight)
+            } else {
+                link.book(current_time, &fragment, weight)
+            };
+            fragment.queuing_delay += start_time - current_time;
+            let jitter = if link.jitter > 0.0 { self.rng.next_f64() * link.jitter } else { 0.0 };
+            let arrival_time = finish_time + link.latency + jitter + link.spike_delay(current_time);
+            link.bytes_sent += wire_size;
+            link.busy_time += link.transmission_time_at(wire_size, start_time);
+            if link.loss_rate > 0.0 && self.rng.next_f64() < link.loss_rate {
+                self.record_drop(fragment, DropReason::LinkLoss);
+                continue;
+            }
+            link.bytes_delivered += wire_size;
+            if link.duplication_probability > 0.0 && self.rng.next_f64() < link.duplication_probability
+                && self.push_event(arrival_time, fragment.clone(), EventType::PacketArrival(next_hop)) {
+                self.in_flight += 1;
+            }
+
+            if self.push_event(arrival_time, fragment, EventType::PacketArrival(next_hop)) {
+                self.in_flight += 1;
+            }
+            self.peak_in_flight = self.peak_in_flight.max(self.in_flight);
+            if !displaced.is_empty() {
+                self.delay_arrivals(node_id, next_hop, &displaced);
+            }
+        }
+
+        if let Some(reverse) = reverse {
+            self.links[reverse].queue_end_time = self.links[index].queue_end_time;
+        }
+        true
+    }
+
+    /// Pushes back the arrivals at `to` of packets the WFQ link from `from`
+    /// has rescheduled.
+    fn delay_arrivals(&mut self, from: usize, to: usize, displaced: &[Displacement]) {
+        let mut events = std::mem::take(&mut self.event_queue).into_vec();
+        for event in &mut events {
+            if event.event_type != EventType::PacketArrival(to) || event.packet.path.last() != Some(&from) { continue; }
+            if let Some((_, _, delay)) = displaced.iter().find(|(id, offset, _)| *id == event.packet.id && *offset == event.packet.fragment) {
+                event.time += delay;
+                event.packet.queuing_delay += delay;
+            }
+        }
+        self.event_queue = events.into();
+    }
+
+    /// Processes events up to absolute time `duration`. Events scheduled later stay
+    /// queued for the next call.
+    pub fn run_simulation(&mut self, duration: f64) -> RunSummary {
+        while let Some(event) = self.event_queue.pop() {
+            if event.time > duration {
+                self.event_queue.push(event);
+                break;
+            }
+            self.current_time = event.time;
+            self.events_processed += 1;
+            if matches!(event.event_type, EventType::PacketArrival(_)) {
+                self.in_flight -= 1;
+            }
+            // Destination arrivals are logged once reassembled, and sends as they leave the source
+            let delivery = matches!(event.event_type, EventType::PacketArrival(node) if node == event.packet.destination_id);
+            if self.record_events && !delivery && !matches!(event.event_type, EventType::ScheduledSend(_)) {
+                self.event_log.push(event.clone());
+            }
+            
+            match event.event_type {
+                EventType::ScheduledSend(node_id) => {
+                    self.forward_packet(node_id, event.packet);
+                }
+                EventType::PacketArrival(node_id) if !event.packet.multicast_routes.is_empty() => {
+                    self.handle_multicast_arrival(node_id, event.packet);
+                }
+                EventType::PacketArrival(node_id) => {
+                    if node_id == event.packet.destination_id {
+                        let key = (event.packet.source_id, event.packet.destination_id, event.packet.sequence);
+                        let mut packet = if let Some(offset) = event.packet.fragment {
+                            let Some(entry) = self.reassembly.get_mut(&event.packet.id) else {
+                                // Its packet was reassembled and delivered already
+                                if self.received_sequences.contains(&key) {
+                                    self.duplicates_detected += 1;
+                                }
+                                continue;
+                            };
+                            if entry.received.insert(offset, event.packet.size_bytes).is_some() {
+                                self.duplicates_detected += 1;
+                                continue;
+                            }
+                            entry.packet.ecn_marked |= event.packet.ecn_marked;
+                            if entry.received.values().sum::<usize>() < entry.packet.size_bytes { continue; }
+                            match self.reassembly.remove(&event.packet.id) {
+                                // The last fragment's route and queuing stand in for the whole packet
+                                Some(entry) => DataPacket {
+                                    path: event.packet.path,
+                                    queuing_delay: event.packet.queuing_delay,
+                                    ..entry.packet
+                                },
+                                None => continue,
+                            }
+                        } else {
+                            event.packet
+                        };
+                        if !self.received_sequences.insert(key) {
+                            self.duplicates_detected += 1;
+                            continue;
+                        }
+                        packet.path.push(node_id);
+                        self.unacked.remove(&packet.id);
+                        if self.dropped_ids.remove(&packet.id) {
+                            // The copies lost on the way were recovered, so they weren't drops
+                            self.dropped_packets.retain(|(p, _)| p.id != packet.id);
+                        }
+
+                        let latency = self.current_time - packet.created_at;
+                        if self.record_events {
+                            let event = self.new_event(
+                                self.current_time,
+                                packet.clone(),
+                                EventType::PacketArrival(node_id),
+                            );
+                            self.event_log.push(event);
+                        }
+                        if self.verbose {
+                            println!("[{:.4}s] {:?} packet (ID {}) arrived at {} | Latency: {:.2} ms{}", 
+                                     self.current_time, packet.packet_type, packet.id, self.get_node_name(node_id), latency * 1000.0,
+                                     if packet.ecn_marked { " | ECN CE" } else { "" });
+                        }
+                        
+                        match packet.packet_type {
+                            PacketType::TcpSyn => {
+                                self.pending_handshakes.insert((packet.source_id, node_id), packet.created_at);
+                                self.send_packet_ex(node_id, packet.source_id, 64, PacketType::TcpSynAck);
+                            }
+                            PacketType::TcpSynAck => {
+                                self.send_packet_ex(node_id, packet.source_id, 64, PacketType::TcpAck);
+                            }
+                            PacketType::TcpAck => {
+                                self.complete_handshake(packet.source_id, node_id);
+                                self.stream_acked(node_id, packet.source_id);
+                            }
+                            PacketType::CdnRequest => {
+                                self.serve_cdn_request(node_id, &packet);
+                            }
+                            PacketType::CdnResponse => {
+                                self.record_ttfb(node_id, &packet);
+                                self.complete_origin_fetch(node_id, &packet);
+                            }
+                            PacketType::DnsQuery => {
+                                let answer = DataPacket {
+                                    content_id: packet.content_id,
+                                    ..self.new_packet(node_id, packet.source_id, 128, PacketType::DnsResponse, self.current_time)
+                                };
+                                self.forward_packet(node_id, answer);
+                            }
+                            PacketType::DnsResponse => {
+                                self.complete_lookup(node_id, &packet);
+                            }
+                            PacketType::Standard => {
+                                self.receive_segment(node_id, &packet);
+                            }
+                        }
+                        
+                        self.completed_packets.push((packet, latency));
+                    } else {
+                        let delay = self.service_time(node_id);
+                        self.push_event(
+                            self.current_time + delay,
+                            event.packet,
+                            EventType::PacketTransmissionComplete(node_id),
+                        );
+                    }
+                }
+                EventType::PacketTransmissionComplete(node_id) => {
+                    self.forward_packet(node_id, event.packet);
+                }
+                EventType::RetransmitTimeout(node_id) => {
+                    self.handle_retransmit_timeout(node_id, event.packet);
+                }
+                EventType::DelayedAck(node_id) => {
+                    let from = event.packet.source_id;
+                    if self.tcp_streams.get(&(from, node_id)).is_some_and(|s| s.ack_deadline == Some(event.time)) {
+                        self.send_stream_ack(node_id, from);
+                    }
+                }
+                EventType::Dropped(..) => {}
+            }
+        }
+
+        self.summarize(0.0)
+    }
+
+    /// Cheap running counters, safe to poll between `run_simulation` steps.
+    pub fn metrics_snapshot(&self) -> Metrics {
+        Metrics {
+            current_time: self.current_time,
+            nodes: self.servers.len() + self.clients.len(),
+            links: self.links.len(),
+            events_processed: self.events_processed,
+            queued_events: self.event_queue.len(),
+            in_flight: self.in_flight,
+            delivered: self.completed_packets.len(),
+            dropped: self.dropped_packets.len(),
+        }
+    }
+
+    /// Where packet `packet_id` is at `time` on the link it's crossing: at the
+    /// sender while queued or serializing, then moving along the great circle
+    /// at the link's propagation delay. For a fragmented packet, the fragment
+    /// due first. `None` if it isn't on a link.
+    pub fn packet_position(&self, packet_id: usize, time: f64) -> Option<GeoLocation> {
+        let (event, to) = self.event_queue.iter()
+            .filter_map(|event| match event.event_type {
+                EventType::PacketArrival(to) if event.packet.id == packet_id => Some((event, to)),
+                _ => None,
+            })
+            .min_by(|a, b| a.0.time.partial_cmp(&b.0.time).unwrap_or(Ordering::Equal))?;
+        let from = *event.packet.path.last()?;
+        let link = self.links.iter().find(|l| l.from == from && l.to == to)?;
+        let progress = if link.latency > 0.0 { 1.0 - (event.time - time) / link.latency } else { 1.0 };
+        Some(self.location_of(from)?.interpolate(self.location_of(to)?, progress.clamp(0.0, 1.0)))
+    }
+
+    /// Most packets (or fragments) ever on links at once; a floor for total buffer sizing.
+    pub fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight
+    }
+
+    /// Re-executes a recorded `event_log` without routing, queuing or drawing
+    /// randomness: the simulation is `reset`, then the log is walked in order,
+    /// advancing the clock to each event's recorded time. Deliveries and drops
+    /// come from the log with the paths and times they had, and the same
+    /// bookkeeping applies as in the live run, so a copy delivered after a lost
+    /// one still takes the loss back and a spare copy of a delivered packet
+    /// isn't a drop. Only the delivered and dropped sets are rebuilt; caches,
+    /// connections and link counters stay as `reset` left them.
+    pub fn replay(&mut self, log: &[Event]) -> RunSummary {
+        self.reset();
+        let record_events = std::mem::replace(&mut self.record_events, false);
+        for event in log {
+            self.current_time = event.time;
+            match event.event_type {
+                EventType::Dropped(_, reason) => self.record_drop(event.packet.clone(), reason),
+                EventType::PacketArrival(node_id) if node_id == event.packet.destination_id => {
+                    let packet = event.packet.clone();
+                    self.received_sequences.insert((packet.source_id, packet.destination_id, packet.sequence));
+                    if self.dropped_ids.remove(&packet.id) {
+                        self.dropped_packets.retain(|(p, _)| p.id != packet.id);
+                    }
+                    let latency = event.time - packet.created_at;
+                    self.completed_packets.push((packet, latency));
+                }
+                _ => {}
+            }
+        }
+        self.record_events = record_events;
+        if record_events {
+            self.event_log = log.to_vec();
+        }
+        self.summarize(0.0)
+    }
+
+    /// Reordering seen at destinations among packets created after `warmup_time`,
+    /// for one (source, destination) flow or, with `None`, summed over every flow.
+    pub fn reordering_stats(&self, flow: Option<(usize, usize)>, warmup_time: f64) -> ReorderStats {
+        let mut highest: HashMap<(usize, usize), u64> = HashMap::new();
+        let mut stats = ReorderStats::default();
+        for (packet, _) in &self.completed_packets {
+            let key = (packet.source_id, packet.destination_id);
+            if flow.is_some_and(|f| f != key) || packet.created_at < warmup_time { continue; }
+            stats.delivered += 1;
+            match highest.get_mut(&key) {
+                Some(max) if packet.sequence < *max => {
+                    stats.out_of_order += 1;
+                    stats.max_extent = stats.max_extent.max(*max - packet.sequence);
+                }
+                Some(max) => *max = packet.sequence,
+                None => { highest.insert(key, packet.sequence); }
+            }
+        }
+        stats
+    }
+
+    /// Latencies of delivered packets created at or after `warmup_time`, so the
+    /// empty-network startup transient doesn't bias steady-state metrics.
+    fn steady_state_latencies(&self, warmup_time: f64) -> Vec<f64> {
+        self.completed_packets.iter()
+            .filter(|(p, _)| p.created_at >= warmup_time)
+            .map(|(_, l)| *l)
+            .collect()
+    }
+
+    /// Nearest-rank latency percentile (0-100) over packets created after `warmup_time`.
+    pub fn latency_percentile(&self, percentile: f64, warmup_time: f64) -> f64 {
+        let mut latencies = self.steady_state_latencies(warmup_time);
+        if latencies.is_empty() { return 0.0; }
+        latencies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        nearest_rank(&latencies, percentile)
+    }
+
+    /// Delivered packets (with their latencies) sent from `src` to `dst`, in delivery order.
+    pub fn completed_for_flow(&self, src: usize, dst: usize) -> impl Iterator<Item = &(DataPacket, f64)> {
+        self.completed_packets.iter().filter(move |(p, _)| p.source_id == src && p.destination_id == dst)
+    }
+
+    /// Delivered packets (with their latencies) of one type, in delivery order.
+    pub fn completed_of_type(&self, packet_type: PacketType) -> impl Iterator<Item = &(DataPacket, f64)> {
+        self.completed_packets.iter().filter(move |(p, _)| p.packet_type == packet_type)
+    }
+
+    /// Checks the p99 latency of every delivered `src` -> `dst` packet against a
+    /// budget. A flow with no deliveries fails.
+    pub fn check_sla(&self, src: usize, dst: usize, p99_budget_ms: f64) -> SlaResult {
+        let mut latencies: Vec<f64> = self.completed_for_flow(src, dst)
+            .map(|(_, latency)| *latency)
+            .collect();
+        latencies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        let measured_p99_ms = if latencies.is_empty() { f64::INFINITY } else { ms(nearest_rank(&latencies, 99.0)) };
+        SlaResult {
+            passed: measured_p99_ms <= p99_budget_ms,
+            measured_p99_ms,
+            budget_ms: p99_budget_ms,
+            margin_ms: p99_budget_ms - measured_p99_ms,
+            samples: latencies.len(),
+        }
+    }
+
+    /// Region tag of a server or client.
+    pub fn region_of(&self, id: usize) -> Option<&str> {
+        if let Some(s) = self.servers.get(&id) { Some(&s.region) }
+        else { self.clients.get(&id).map(|c| c.region.as_str()) }
+    }
+
+    /// Link bandwidth (bps) summed per region: links inside a region count
+    /// under its name, links between regions under "From -> To".
+    pub fn backbone_capacity_by_region(&self) -> HashMap<String, f64> {
+        let mut capacity = HashMap::new();
+        for link in &self.links {
+            let from = self.region_of(link.from).unwrap_or("Unknown");
+            let to = self.region_of(link.to).unwrap_or("Unknown");
+            let key = if from == to { from.to_string() } else { format!("{} -> {}", from, to) };
+            *capacity.entry(key).or_insert(0.0) += link.bandwidth;
+        }
+        capacity
+    }
+
+    /// Delivered packets created after `warmup_time`, counted by destination region.
+    pub fn packets_delivered_by_region(&self, warmup_time: f64) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for (packet, _) in self.completed_packets.iter().filter(|(p, _)| p.created_at >= warmup_time) {
+            let region = self.region_of(packet.destination_id).unwrap_or("Unknown");
+            *counts.entry(region.to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Mean latency of packets created after `warmup_time`, by destination region.
+    pub fn avg_latency_by_region(&self, warmup_time: f64) -> BTreeMap<String, f64> {
+        let mut totals: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+        for (packet, latency) in self.completed_packets.iter().filter(|(p, _)| p.created_at >= warmup_time) {
+            let region = self.region_of(packet.destination_id).unwrap_or("Unknown");
+            let entry = totals.entry(region.to_string()).or_insert((0.0, 0));
+            entry.0 += latency;
+            entry.1 += 1;
+        }
+        totals.into_iter().map(|(region, (total, count))| (region, total / count as f64)).collect()
+    }
+
+    /// How many packets were dropped for each reason over the whole run.
+    pub fn drop_breakdown(&self) -> HashMap<DropReason, usize> {
+        let mut counts = HashMap::new();
+        for (_, reason) in &self.dropped_packets {
+            *counts.entry(*reason).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Delivered packets created after `warmup_time`, grouped by type.
+    pub fn latency_by_type(&self, warmup_time: f64) -> BTreeMap<PacketType, TypeLatency> {
+        let mut groups: BTreeMap<PacketType, Vec<f64>> = BTreeMap::new();
+        for (packet, latency) in self.completed_packets.iter().filter(|(p, _)| p.created_at >= warmup_time) {
+            groups.entry(packet.packet_type).or_default().push(*latency);
+        }
+        groups.into_iter()
+            .map(|(p_type, mut latencies)| {
+                latencies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let stats = TypeLatency {
+                    count: latencies.len(),
+                    avg_latency: latencies.iter().sum::<f64>() / latencies.len() as f64,
+                    p95_latency: nearest_rank(&latencies, 95.0),
+                };
+                (p_type, stats)
+            })
+            .collect()
+    }
+
+    /// Flows from `send_flow` whose every packet has been delivered, by flow id.
+    pub fn flow_completion_times(&self) -> Vec<FlowCompletion> {
+        let mut flows: BTreeMap<u64, FlowCompletion> = BTreeMap::new();
+        for (packet, latency) in &self.completed_packets {
+            let Some(flow_id) = packet.flow_id else { continue };
+            let flow = flows.entry(flow_id).or_insert(FlowCompletion {
+                flow_id,
+                packets: 0,
+                bytes: 0,
+                started_at: packet.created_at,
+                fct: 0.0,
+            });
+            let finished_at = (flow.started_at + flow.fct).max(packet.created_at + latency);
+            flow.started_at = flow.started_at.min(packet.created_at);
+            flow.fct = finished_at - flow.started_at;
+            flow.packets += 1;
+            flow.bytes += packet.size_bytes;
+        }
+        flows.into_values()
+            .filter(|flow| self.flow_packets.get(&flow.flow_id) == Some(&flow.packets))
+            .collect()
+    }
+
+    /// Completion times of finished flows split at `small_flow_bytes`: flows up
+    /// to that size are short (latency-bound), larger ones long (throughput-bound).
+    pub fn fct_distribution(&self, small_flow_bytes: usize) -> (FctStats, FctStats) {
+        let (mut small, mut large) = (Vec::new(), Vec::new());
+        for flow in self.flow_completion_times() {
+            if flow.bytes <= small_flow_bytes { small.push(flow.fct) } else { large.push(flow.fct) }
+        }
+        let stats = |mut fcts: Vec<f64>| {
+            if fcts.is_empty() { return FctStats::default(); }
+            fcts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            FctStats {
+                flows: fcts.len(),
+                avg_fct: fcts.iter().sum::<f64>() / fcts.len() as f64,
+                p99_fct: nearest_rank(&fcts, 99.0),
+            }
+        };
+        (stats(small), stats(large))
+    }
+
+    /// Delivered goodput in bits per second over the post-warm-up window.
+    pub fn throughput_bps(&self, warmup_time: f64) -> f64 {
+        let window = self.current_time - warmup_time;
+        if window <= 0.0 { return 0.0; }
+        let bytes: usize = self.completed_packets.iter()
+            .filter(|(p, _)| p.created_at >= warmup_time)
+            .map(|(p, _)| p.size_bytes)
+            .sum();
+        bytes as f64 * 8.0 / window
+    }
+
+    fn summarize(&self, warmup_time: f64) -> RunSummary {
+        let latencies = self.steady_state_latencies(warmup_time);
+        let avg_latency = if latencies.is_empty() { 0.0 } else { latencies.iter().sum::<f64>() / latencies.len() as f64 };
+        RunSummary {
+            delivered: latencies.len(),
+            dropped: self.dropped_packets.iter().filter(|(p, _)| p.created_at >= warmup_time).count(),
+            avg_latency,
+            max_latency: latencies.iter().fold(0.0f64, |a, b| a.max(*b)),
+            p99_latency: self.latency_percentile(99.0, warmup_time),
+            duration: self.current_time,
+        }
+    }
+
+    pub fn analyze_results(&self, warmup_time: f64) {
+        println!("\n=== Simulation Results ===");
+        let summary = self.summarize(warmup_time);
+        if summary.delivered == 0 { return; }
+        
+        let total_capacity: f64 = self.servers.values().map(|s| s.bandwidth).sum();
+        
+        if warmup_time > 0.0 {
+            println!(
+                "Warm-up window: {:.3} s ({} packets excluded)",
+                warmup_time,
+                self.completed_packets.len() - summary.delivered
+            );
+        }
+        println!("Simulated time: {}", format_duration(summary.duration));
+        println!("Total delivered: {}", summary.delivered);
+        println!("Total dropped: {}", summary.dropped);
+        println!("Total Capacity: {:.2} Gbps", total_capacity / 1_000_000_000.0);
+        let mut backbone: Vec<(String, f64)> = self.backbone_capacity_by_region().into_iter().collect();
+        backbone.sort_by(|a, b| a.0.cmp(&b.0));
+        for (region, bandwidth) in backbone {
+            println!("  Backbone {}: {:.2} Gbps", region, bandwidth / 1_000_000_000.0);
+        }
+        println!("Avg Latency: {:.2} ms", ms(summary.avg_latency));
+        println!("P50 Latency: {:.2} ms", ms(self.latency_percentile(50.0, warmup_time)));
+        println!("P99 Latency: {:.2} ms", ms(summary.p99_latency));
+        println!("Max Latency: {:.2} ms", ms(summary.max_latency));
+        println!("Throughput: {:.2} Mbps", self.throughput_bps(warmup_time) / 1_000_000.0);
+        let queuing: Vec<f64> = self.completed_packets.iter()
+            .filter(|(p, _)| p.created_at >= warmup_time)
+            .map(|(p, _)| p.queuing_delay)
+            .collect();
+        println!(
+            "Queuing Delay: avg {:.2} ms | max {:.2} ms",
+            ms(queuing.iter().sum::<f64>() / queuing.len() as f64),
+            ms(queuing.iter().fold(0.0, |a: f64, b| a.max(*b)))
+        );
+        println!("Peak in flight: {} packets", self.peak_in_flight());
+        let marked = self.completed_packets.iter()
+            .filter(|(p, _)| p.created_at >= warmup_time && p.ecn_marked)
+            .count();
+        if marked > 0 {
+            println!("ECN-marked deliveries: {} ({:.1}%)", marked, marked as f64 / summary.delivered as f64 * 100.0);
+        }
+        let reordering = self.reordering_stats(None, warmup_time);
+        if reordering.out_of_order > 0 {
+            println!("Out-of-order arrivals: {} ({:.2}%) | max extent {} packets",
+                     reordering.out_of_order,
+                     reordering.out_of_order as f64 / reordering.delivered as f64 * 100.0,
+                     reordering.max_extent);
+        }
+        if self.retransmissions > 0 {
+            println!("Retransmissions: {}", self.retransmissions);
+        }
+        if self.duplicates_detected > 0 {
+            println!("Duplicates discarded: {}", self.duplicates_detected);
+        }
+        let energy = self.total_energy_joules();
+        println!("Energy: {:.2} kJ ({:.2} J per delivered packet, {:.2} kJ counting every bit sent)",
+                 energy / 1000.0, energy / summary.delivered as f64, self.transmitted_energy_joules() / 1000.0);
+        let transfer_cost = self.total_transfer_cost();
+        if transfer_cost > 0.0 {
+            println!("Transit cost: ${:.4}", transfer_cost);
+        }
+        let tags: BTreeSet<&str> = self.links.iter().flat_map(|l| l.tags.iter().map(String::as_str)).collect();
+        for tag in tags {
+            let stats = self.stats_by_tag(tag);
+            println!("Links tagged {}: {} links | {:.2} MB carried | utilization avg {:.2}% max {:.2}%",
+                     tag, stats.links, stats.bytes_carried as f64 / 1e6, stats.avg_utilization * 100.0, stats.max_utilization * 100.0);
+        }
+        if self.cache_hits + self.cache_misses > 0 {
+            println!("CDN Cache: {} hits / {} misses", self.cache_hits, self.cache_misses);
+        }
+
+        let mut drops: Vec<(DropReason, usize)> = self.drop_breakdown().into_iter().collect();
+        if !drops.is_empty() {
+            drops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            println!("\n--- Drop Reasons (whole run) ---");
+            for (reason, count) in drops {
+                println!("{:<18} | {:>7} ({:.1}%)", format!("{:?}", reason), count, count as f64 / self.dropped_packets.len() as f64 * 100.0);
+            }
+        }
+
+        println!("\n--- By Destination Region ---");
+        let region_latency = self.avg_latency_by_region(warmup_time);
+        for (region, count) in self.packets_delivered_by_region(warmup_time) {
+            println!("{:<14} | {:>7} delivered | avg {:>8.2} ms", region, count, ms(region_latency[&region]));
+        }
+
+        println!("\n--- Latency by Packet Type ---");
+        for (p_type, stats) in self.latency_by_type(warmup_time) {
+            println!("{:<12} | {:>7} delivered | avg {:>8.2} ms | p95 {:>8.2} ms",
+                     format!("{:?}", p_type), stats.count, ms(stats.avg_latency), ms(stats.p95_latency));
+        }
+
+        let mut flows: BTreeMap<(usize, usize), (f64, usize)> = BTreeMap::new();
+        for (packet, latency) in self.completed_packets.iter().filter(|(p, _)| p.created_at >= warmup_time) {
+            let entry = flows.entry((packet.source_id, packet.destination_id)).or_insert((0.0, 0));
+            entry.0 += latency;
+            entry.1 += 1;
+        }
+        println!("\n--- Latency vs. Physical Floor (vacuum light along route) ---");
+        for ((from, to), (total, count)) in flows {
+            let floor = self.theoretical_min_latency(from, to);
+            if floor <= 0.0 || !floor.is_finite() { continue; }
+            let avg = total / count as f64;
+            println!(
+                "{} -> {} | avg {:.2} ms | floor {:.2} ms | {:.2}x",
+                self.get_node_name(from),
+                self.get_node_name(to),
+                ms(avg),
+                ms(floor),
+                avg / floor
+            );
+        }
+
+        let utilization = self.link_utilization();
+
+        println!("\n--- Directional Link Utilization ---");
+        for (link, (_, _, busy)) in self.links.iter().zip(&utilization) {
+            println!(
+                "{} -> {} | {:.2} Gbps | {:.2} MB sent | {:.2}% utilized",
+                self.get_node_name(link.from),
+                self.get_node_name(link.to),
+                link.bandwidth / 1_000_000_000.0,
+                link.bytes_sent as f64 / 1_000_000.0,
+                busy * 100.0
+            );
+        }
+
+        let mut bottlenecks = utilization;
+        bottlenecks.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+        println!("\n--- Top Bottleneck Links ---");
+        for (from, to, busy) in bottlenecks.into_iter().take(3) {
+            println!("{} -> {} | {:.2}% busy", self.get_node_name(from), self.get_node_name(to), busy * 100.0);
+        }
+    }
+
+    /// Energy used so far: every server's power draw over the elapsed simulated
+    /// time plus each link's per-bit cost for the bits of delivered packets, on
+    /// every hop they took. Lost, dropped and duplicate copies aren't counted;
+    /// `transmitted_energy_joules` includes them.
+    pub fn total_energy_joules(&self) -> f64 {
+        let delivered: f64 = self.completed_packets.iter()
+            .flat_map(|(packet, _)| packet.path.windows(2).map(move |hop| (packet.size_bytes, hop[0], hop[1])))
+            .filter_map(|(size, from, to)| self.get_link(from, to).map(|l| size as f64 * 8.0 * l.energy_per_bit))
+            .sum();
+        self.idle_energy_joules() + delivered
+    }
+
+    /// Like `total_energy_joules`, but charging every bit the links sent,
+    /// whether or not it arrived.
+    pub fn transmitted_energy_joules(&self) -> f64 {
+        let transmission: f64 = self.links.iter().map(|l| l.bytes_sent as f64 * 8.0 * l.energy_per_bit).sum();
+        self.idle_energy_joules() + transmission
+    }
+
+    fn idle_energy_joules(&self) -> f64 {
+        self.servers.values().map(|s| s.power_watts * self.current_time).sum()
+    }
+
+    /// Dollars owed for transit so far: every byte a metered link has delivered,
+    /// at its `cost_per_gb`. Bytes the link lost on the way aren't billed.
+    pub fn total_transfer_cost(&self) -> f64 {
+        self.links.iter().map(|l| l.bytes_delivered as f64 / 1e9 * l.cost_per_gb).sum()
+    }
+
+    /// Fraction of the run each link spent transmitting, as (from, to, fraction_busy).
+    pub fn link_utilization(&self) -> Vec<(usize, usize, f64)> {
+        self.links.iter()
+            .map(|link| {
+                let busy = if self.current_time > 0.0 { link.busy_time / self.current_time } else { 0.0 };
+                (link.from, link.to, busy)
+            })
+            .collect()
+    }
+
+    /// Bytes carried and utilization over the run, summed or averaged across
+    /// every link tagged `tag`.
+    pub fn stats_by_tag(&self, tag: &str) -> LinkStats {
+        let tagged: Vec<(usize, f64)> = self.links.iter()
+            .filter(|l| l.tags.iter().any(|t| t == tag))
+            .map(|l| (l.bytes_sent, if self.current_time > 0.0 { l.busy_time / self.current_time } else { 0.0 }))
+            .collect();
+        if tagged.is_empty() { return LinkStats::default(); }
+        LinkStats {
+            links: tagged.len(),
+            bytes_carried: tagged.iter().map(|(bytes, _)| bytes).sum(),
+            avg_utilization: tagged.iter().map(|(_, busy)| busy).sum::<f64>() / tagged.len() as f64,
+            max_utilization: tagged.iter().map(|(_, busy)| *busy).fold(0.0, f64::max),
+        }
+    }
+
+    /// Serializes aggregate stats plus every delivered packet as a JSON document
+    /// for dashboards. Written by hand to keep the crate dependency-free.
+    pub fn export_results_json(&self) -> String {
+        let summary = self.summarize(0.0);
+        let mut json = String::from("{\n  \"aggregate\": {\n");
+        json.push_str(&format!("    \"delivered\": {},\n", summary.delivered));
+        json.push_str(&format!("    \"dropped\": {},\n", summary.dropped));
+        json.push_str(&format!("    \"duration_s\": {},\n", json_number(summary.duration)));
+        json.push_str(&format!("    \"avg_latency_ms\": {},\n", json_number(summary.avg_latency * 1000.0)));
+        json.push_str(&format!("    \"p50_latency_ms\": {},\n", json_number(self.latency_percentile(50.0, 0.0) * 1000.0)));
+        json.push_str(&format!("    \"p99_latency_ms\": {},\n", json_number(summary.p99_latency * 1000.0)));
+        json.push_str(&format!("    \"max_latency_ms\": {},\n", json_number(summary.max_latency * 1000.0)));
+        json.push_str(&format!("    \"throughput_bps\": {}\n", json_number(self.throughput_bps(0.0))));
+        json.push_str("  },\n  \"packets\": [");
+
+        for (i, (packet, latency)) in self.completed_packets.iter().enumerate() {
+            let path: Vec<String> = packet.path.iter().map(|id| id.to_string()).collect();
+            json.push_str(if i == 0 { "\n" } else { ",\n" });
+            json.push_str(&format!(
+                "    {{\"id\": {}, \"src\": {}, \"dst\": {}, \"latency_ms\": {}, \"path\": [{}]}}",
+                packet.id,
+                packet.source_id,
+                packet.destination_id,
+                json_number(latency * 1000.0),
+                path.join(", ")
+            ));
+        }
+        if !self.completed_packets.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("]\n}\n");
+        json
+    }
+}
+
+/// Stand-in for hashing real bytes: the same content at the same size always
+/// fingerprints the same. Never 0, which means "no content".
+fn payload_hash(content_id: u64, size_bytes: usize) -> u64 {
+    let mut hash = content_id ^ (size_bytes as u64).rotate_left(32);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (hash ^ (hash >> 31)).max(1)
+}
+
+/// JSON has no NaN/Infinity, so non-finite values are written as null.
+fn json_number(value: f64) -> String {
+    if value.is_finite() { format!("{:.6}", value) } else { "null".to_string() }
+}
+
+/// Two-sided 95% critical value of Student's t with `df` degrees of freedom.
+fn t_critical_95(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+        2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+        2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::INFINITY,
+        1..=30 => TABLE[df - 1],
+        _ => {
+            // Cornish-Fisher expansion around the normal quantile; good to 1e-4 past the table
+            let (z, d) = (1.959_964_f64, df as f64);
+            z + (z.powi(3) + z) / (4.0 * d) + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * d * d)
+        }
+    }
+}
+
+/// Nearest-rank percentile of already-sorted, non-empty `values`.
+fn nearest_rank(values: &[f64], percentile: f64) -> f64 {
+    let rank = ((percentile / 100.0) * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1780 @@
+use super::*;
+use crate::topogen;
+
+/// Hub 1 between leaves 2 and 3, so 2 -> 3 crosses two links.
+fn two_hop() -> NetworkSimulation {
+    let mut sim = topogen::star(3);
+    sim.verbose = false;
+    sim
+}
+
+#[test]
+fn duplicated_copies_are_detected() {
+    let mut sim = two_hop();
+    sim.set_link_duplication(2, 1, 1.0);
+    sim.send_packet_ex(2, 3, 1000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert!(sim.duplicates_detected >= 1);
+}
+
+#[test]
+fn duplicated_fragments_are_detected() {
+    let mut sim = two_hop();
+    sim.set_link_duplication(1, 3, 1.0);
+    sim.send_packet_ex(2, 3, 4000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert_eq!(sim.duplicates_detected, 3);
+}
+
+#[test]
+fn refragmented_packet_is_reassembled() {
+    let mut sim = two_hop();
+    sim.set_link_mtu(1, 3, 576);
+    sim.send_packet_ex(2, 3, 9000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert_eq!(sim.completed_packets[0].0.size_bytes, 9000);
+    assert_eq!(sim.duplicates_detected, 0);
+}
+
+#[test]
+fn retransmission_recovers_fragmented_packets_on_lossy_link() {
+    let mut sim = two_hop();
+    sim.retransmit = Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 8 });
+    sim.set_link_loss(1, 3, 0.3);
+    for _ in 0..20 {
+        sim.send_packet_ex(2, 3, 9000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 20);
+    assert!(sim.retransmissions > 0);
+    assert!(sim.dropped_packets.is_empty(), "recovered losses still counted: {:?}", sim.drop_breakdown());
+}
+
+/// Leaves 2 and 3 both send 100 1000-byte packets to leaf 4 through hub 1,
+/// whose link to 4 is a 1 Mbps weighted-fair bottleneck.
+fn weighted_fair_bottleneck(weight_2: f64, weight_3: f64) -> NetworkSimulation {
+    let mut sim = topogen::star(4);
+    sim.verbose = false;
+    sim.set_bandwidth_schedule(1, 4, vec![(0.0, 1e6)]);
+    sim.set_link_scheduler(1, 4, LinkScheduler::WeightedFair);
+    sim.set_flow_weight(2, 4, weight_2);
+    sim.set_flow_weight(3, 4, weight_3);
+    for _ in 0..100 {
+        sim.send_packet_ex(2, 4, 1000, PacketType::Standard);
+        sim.send_packet_ex(3, 4, 1000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    sim
+}
+
+#[test]
+fn weighted_fair_shares_follow_weights() {
+    let sim = weighted_fair_bottleneck(3.0, 1.0);
+    assert_eq!(sim.completed_packets.len(), 200);
+    let mut arrivals: Vec<(f64, usize)> = sim.completed_packets.iter()
+        .map(|(p, latency)| (p.created_at + latency, p.source_id))
+        .collect();
+    arrivals.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let heavy = arrivals[..100].iter().filter(|(_, source)| *source == 2).count();
+    assert!((70..=80).contains(&heavy), "weight-3 flow got {} of the first 100", heavy);
+}
+
+#[test]
+fn weighted_fair_never_exceeds_link_bandwidth() {
+    let sim = weighted_fair_bottleneck(3.0, 1.0);
+    let mut arrivals: Vec<f64> = sim.completed_packets.iter().map(|(p, latency)| p.created_at + latency).collect();
+    arrivals.sort_by(f64::total_cmp);
+    let serialization = 1000.0 * 8.0 / 1e6;
+    assert!(arrivals.windows(2).all(|pair| pair[1] - pair[0] >= serialization * (1.0 - 1e-9)));
+}
+
+#[test]
+fn non_positive_flow_weights_are_ignored() {
+    let mut sim = two_hop();
+    sim.set_flow_weight(2, 3, 0.0);
+    sim.set_flow_weight(2, 3, -1.0);
+    assert!(sim.flow_weights.is_empty());
+    let sim = weighted_fair_bottleneck(0.0, 1.0);
+    assert_eq!(sim.completed_packets.len(), 200);
+}
+
+#[test]
+fn ttfb_excludes_last_hop_serialization() {
+    let mut sim = two_hop();
+    sim.set_bandwidth_schedule(1, 2, vec![(0.0, 1e6)]);
+    let request = sim.send_cdn_request(2, 3, 7);
+    sim.run_simulation(f64::INFINITY);
+    let (response, latency) = sim.completed_of_type(PacketType::CdnResponse).next().expect("response delivered");
+    let arrived = response.created_at + latency;
+    let serialization = 1024.0 * 8.0 / 1e6;
+    assert!((sim.ttfb(request).unwrap() - (arrived - serialization)).abs() < 1e-9);
+}
+
+/// Poisson traffic from 2 to 3 over a jittery, lossy hop, so the outcome
+/// depends on the RNG.
+fn noisy_workload(sim: &mut NetworkSimulation) -> Vec<(usize, f64)> {
+    sim.set_link_jitter(1, 3, 0.002);
+    sim.set_link_loss(1, 3, 0.1);
+    sim.generate_poisson_traffic(2, 3, 500.0, 0.2, &PacketSizeDistribution::Uniform { min: 64, max: 1500 });
+    sim.run_simulation(f64::INFINITY);
+    sim.completed_packets.iter().map(|(p, latency)| (p.id, *latency)).collect()
+}
+
+#[test]
+fn reset_run_matches_fresh_simulation() {
+    let mut reused = two_hop();
+    reused.set_seed(7);
+    reused.preload_cache(1, &[1]);
+    for _ in 0..500 {
+        reused.send_packet_ex(2, 3, 1500, PacketType::Standard);
+    }
+    noisy_workload(&mut reused);
+    reused.reset();
+    assert!(reused.servers[&1].cache.is_empty());
+    assert_eq!(reused.current_time, 0.0);
+
+    let mut fresh = two_hop();
+    fresh.set_seed(7);
+    assert_eq!(noisy_workload(&mut reused), noisy_workload(&mut fresh));
+    assert_eq!(reused.dropped_packets.len(), fresh.dropped_packets.len());
+}
+
+/// (id, latency, path) of every delivery and (id, fragment, reason) of every drop.
+#[allow(clippy::type_complexity)]
+fn run_outcome(sim: &NetworkSimulation) -> (Vec<(usize, f64, Vec<usize>)>, Vec<(usize, Option<usize>, DropReason)>) {
+    let delivered = sim.completed_packets.iter()
+        .map(|(p, latency)| (p.id, *latency, p.path.clone()))
+        .collect();
+    let dropped = sim.dropped_packets.iter()
+        .map(|(p, reason)| (p.id, p.fragment, *reason))
+        .collect();
+    (delivered, dropped)
+}
+
+#[test]
+fn replay_reproduces_recorded_run() {
+    let mut sim = two_hop();
+    sim.set_seed(11);
+    sim.record_events = true;
+    sim.retransmit = Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 4 });
+    sim.set_link_duplication(2, 1, 0.1);
+    sim.send_packet_ex(2, 3, 4000, PacketType::Standard);
+    sim.send_packet_ex(2, 3, 64, PacketType::TcpSyn);
+    sim.send_cdn_request(2, 3, 9);
+    noisy_workload(&mut sim);
+    let recorded = run_outcome(&sim);
+    assert!(sim.retransmissions > 0 && sim.duplicates_detected > 0);
+
+    let log = std::mem::take(&mut sim.event_log);
+    sim.replay(&log);
+    assert_eq!(run_outcome(&sim), recorded);
+
+    // Nothing is routed or drawn again: another seed with the hop to 3 down replays the same run
+    let mut elsewhere = two_hop();
+    elsewhere.set_seed(12);
+    elsewhere.set_link_down(1, 3);
+    let summary = elsewhere.replay(&log);
+    assert_eq!(run_outcome(&elsewhere), recorded);
+    assert_eq!(summary.delivered, recorded.0.len());
+    assert_eq!(elsewhere.current_time, log.last().unwrap().time);
+}
+
+#[test]
+fn precomputed_routes_follow_installed_strategy() {
+    let mut sim = topogen::full_mesh(3);
+    sim.verbose = false;
+    sim.set_routing_strategy(Box::new(CostRouting));
+    sim.set_link_cost(1, 3, 1e6);
+    sim.precompute_routes();
+    assert_eq!(sim.route_next_hop(1, 3), Some(2));
+    assert_eq!(sim.route_next_hop(3, 1), Some(1));
+}
+
+#[test]
+fn event_queue_never_exceeds_max_events() {
+    let mut sim = two_hop();
+    sim.max_events = Some(16);
+    sim.record_events = true;
+    sim.set_link_mtu(1, 3, 1500);
+    sim.generate_poisson_traffic(2, 3, 20_000.0, 0.5, &PacketSizeDistribution::Fixed(9000));
+    assert!(sim.event_queue.len() <= 16);
+    for step in 1..=1000 {
+        sim.run_simulation(step as f64 * 0.001);
+        assert!(sim.event_queue.len() <= 16);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let refused = |sim: &NetworkSimulation, mid_path: bool| sim.dropped_packets.iter()
+        .filter(|(p, reason)| *reason == DropReason::AdmissionControl && p.path.is_empty() != mid_path)
+        .count();
+    assert!(refused(&sim, false) > 0 && refused(&sim, true) > 0);
+
+    let recorded = run_outcome(&sim);
+    let log = std::mem::take(&mut sim.event_log);
+    sim.replay(&log);
+    assert_eq!(run_outcome(&sim), recorded);
+}
+
+#[test]
+fn confidence_interval_is_unbounded_below_two_samples() {
+    for samples in [&[][..], &[0.25][..]] {
+        let interval = ConfidenceInterval::from_samples(samples);
+        assert!(!interval.mean.is_nan());
+        assert_eq!((interval.low, interval.high), (f64::NEG_INFINITY, f64::INFINITY));
+    }
+}
+
+#[test]
+fn replicated_latency_brackets_analytic_mean() {
+    const JITTER: f64 = 0.01;
+    let workload: Vec<SendSpec> = (0..20).map(|i| (2, 3, 1000, PacketType::Standard, i as f64 * 0.1)).collect();
+    let lossy = move |jitter: f64| {
+        let workload = workload.clone();
+        SimConfig {
+            seed: 3,
+            duration: f64::INFINITY,
+            setup: Arc::new(move |sim: &mut NetworkSimulation| {
+                let seed = sim.seed;
+                *sim = two_hop();
+                sim.set_seed(seed);
+                sim.set_link_loss(1, 3, 0.2);
+                sim.set_link_jitter(1, 3, jitter);
+                sim.send_batch(&workload);
+            }),
+        }
+    };
+    // Uniform jitter adds JITTER / 2 on average to the fixed path latency
+    let expected = lossy(0.0).run().avg_latency + JITTER / 2.0;
+    let stats = run_replications(&lossy(JITTER), 30);
+    assert_eq!(stats.replications, 30);
+    assert!(stats.drop_rate.low > 0.0);
+    assert!(stats.avg_latency.contains(expected), "{:?} vs {}", stats.avg_latency, expected);
+}
+
+#[test]
+fn load_sweep_latency_rises_near_bottleneck() {
+    let bottleneck = 100_000_000.0;
+    let mut sim = two_hop();
+    if let Some(link) = sim.links.iter_mut().find(|l| l.from == 1 && l.to == 3) {
+        link.bandwidth = bottleneck;
+    }
+    let rates = [0.1, 0.3, 0.99].map(|share| share * bottleneck);
+    let curve = sim.load_sweep(2, 3, &rates);
+    let (low, moderate, saturated) = (curve[0].1, curve[1].1, curve[2].1);
+    assert!(moderate < low * 1.01);
+    assert!(saturated - low > 50.0 * (moderate - low));
+    assert!(sim.event_queue.is_empty() && sim.completed_packets.is_empty());
+}
+
+#[test]
+fn routing_mode_installs_its_strategy() {
+    let mut sim = topogen::full_mesh(3);
+    sim.verbose = false;
+    sim.set_routing_mode(RoutingMode::UtilizationAware { refresh_interval: 0.005 });
+    assert_eq!(sim.routing.refresh_interval(), Some(0.005));
+    sim.set_routing_mode(RoutingMode::HopCount);
+    assert_eq!(sim.routing.refresh_interval(), None);
+    assert_eq!(sim.route_next_hop(1, 3), Some(3));
+}
+
+#[test]
+fn trace_matches_fragmented_delivery_without_using_an_id() {
+    let mut sim = two_hop();
+    sim.set_link_mtu(1, 3, 576);
+    let next_id = sim.next_packet_id;
+    let trace = sim.trace_packet(2, 3, 9000, PacketType::Standard).expect("route exists");
+    assert_eq!(sim.next_packet_id, next_id);
+    assert_eq!(trace.hops.len(), 2);
+
+    sim.send_packet_ex(2, 3, 9000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    let (packet, latency) = &sim.completed_packets[0];
+    assert_eq!(packet.id, next_id);
+    assert!((trace.total_latency - latency).abs() < 1e-9, "{} vs {}", trace.total_latency, latency);
+}
+
+#[test]
+fn policy_routing_forwards_over_fewest_as_crossings() {
+    // Nodes 1 and 3 sit opposite each other on a ring, reached through 2 or 4 at equal latency
+    for (transit, other) in [(2, 4), (4, 2)] {
+        let mut sim = topogen::ring(4);
+        sim.verbose = false;
+        for id in [1, 3, other] {
+            sim.servers.get_mut(&id).unwrap().as_number = 100;
+        }
+        sim.servers.get_mut(&transit).unwrap().as_number = 200;
+        sim.set_routing_strategy(Box::new(PolicyRouting));
+        assert_eq!(sim.find_next_hop_policy(1, 3), Some(other));
+
+        sim.send_packet_ex(1, 3, 1000, PacketType::Standard);
+        sim.run_simulation(f64::INFINITY);
+        assert_eq!(sim.completed_packets[0].0.path, vec![1, other, 3]);
+    }
+}
+
+#[test]
+fn policy_routing_breaks_as_ties_on_link_cost() {
+    let mut sim = topogen::ring(4);
+    sim.verbose = false;
+    sim.set_link_cost(1, 2, 1e6);
+    assert_eq!(sim.find_next_hop_policy(1, 3), Some(4));
+    sim.set_link_cost(1, 2, 0.0);
+    sim.set_link_cost(1, 4, 1e6);
+    assert_eq!(sim.find_next_hop_policy(1, 3), Some(2));
+}
+
+#[test]
+fn energy_counts_only_delivered_bits() {
+    let mut sim = two_hop();
+    sim.set_link_loss(1, 3, 0.5);
+    sim.set_link_duplication(2, 1, 0.5);
+    let per_packet: f64 = [(2, 1), (1, 3)].iter()
+        .map(|&(from, to)| 1000.0 * 8.0 * sim.get_link(from, to).unwrap().energy_per_bit)
+        .sum();
+    let mut previous = 0.0;
+    for sent in [50, 100] {
+        for _ in 0..50 {
+            sim.send_packet_ex(2, 3, 1000, PacketType::Standard);
+        }
+        sim.run_simulation(sent as f64);
+        let delivered = sim.completed_packets.len() as f64;
+        let transmission = sim.total_energy_joules() - sim.idle_energy_joules();
+        assert!(delivered > previous && delivered < sent as f64);
+        assert!((transmission - delivered * per_packet).abs() < 1e-9 * transmission);
+        assert!(sim.transmitted_energy_joules() > sim.total_energy_joules());
+        previous = delivered;
+    }
+}
+
+#[test]
+fn asymmetric_upload_is_slower_than_download() {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.clear();
+    sim.connect_asymmetric(2, 1, 10_000_000.0, 100_000_000.0);
+    sim.send_packet_ex(2, 1, 1_000_000, PacketType::Standard);
+    sim.send_packet_ex(1, 2, 1_000_000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    let latency = |from: usize| sim.completed_for_flow(from, 3 - from).map(|(_, l)| *l).next().expect("delivered");
+    let (upload, download) = (latency(2), latency(1));
+    assert!(upload > 5.0 * download, "upload {} vs download {}", upload, download);
+}
+
+#[test]
+fn oversized_packet_is_fragmented_and_reassembled() {
+    let mut sim = two_hop();
+    sim.record_events = true;
+    sim.send_packet_ex(2, 3, 9000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    let fragments: HashSet<Option<usize>> = sim.event_log.iter()
+        .filter(|e| matches!(e.event_type, EventType::PacketArrival(1)))
+        .map(|e| e.packet.fragment)
+        .collect();
+    assert_eq!(fragments, (0..6).map(|i| Some(i * 1500)).collect());
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert_eq!(sim.completed_packets[0].0.size_bytes, 9000);
+}
+
+#[test]
+fn bimodal_sizes_follow_large_fraction() {
+    let mut sim = two_hop();
+    let sizes = PacketSizeDistribution::Bimodal { small: 64, large: 1500, large_fraction: 0.3 };
+    let scheduled = sim.generate_poisson_traffic(2, 3, 2_000.0, 1.0, &sizes);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), scheduled);
+    let large = sim.completed_packets.iter().filter(|(p, _)| p.size_bytes == 1500).count();
+    assert!(sim.completed_packets.iter().all(|(p, _)| p.size_bytes == 64 || p.size_bytes == 1500));
+    let fraction = large as f64 / scheduled as f64;
+    assert!((fraction - 0.3).abs() < 0.03, "{} large", fraction);
+}
+
+#[test]
+fn warmup_window_removes_startup_transient() {
+    let mut sim = two_hop();
+    if let Some(link) = sim.links.iter_mut().find(|l| l.from == 1 && l.to == 3) {
+        link.bandwidth = 10_000_000.0;
+    }
+    // 90% load: the queue takes a while to build up from empty
+    sim.generate_poisson_traffic(2, 3, 750.0, 8.0, &PacketSizeDistribution::Fixed(1500));
+    sim.run_simulation(f64::INFINITY);
+    let (full, warm, late) = (sim.summarize(0.0).avg_latency, sim.summarize(2.0).avg_latency, sim.summarize(5.0).avg_latency);
+    assert!(full != warm);
+    assert!((warm - late).abs() < (full - late).abs(), "full {} warm {} late {}", full, warm, late);
+}
+
+#[test]
+fn saturated_link_is_busy_and_idle_link_is_not() {
+    let mut sim = two_hop();
+    for link in sim.links.iter_mut() {
+        link.latency = 0.0001;
+        if link.from == 2 && link.to == 1 {
+            link.bandwidth = 10_000_000.0;
+        }
+    }
+    for _ in 0..100 {
+        sim.send_packet_ex(2, 3, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 100);
+    let utilization = sim.link_utilization();
+    let of = |from, to| utilization.iter().find(|u| u.0 == from && u.1 == to).unwrap().2;
+    assert!(of(2, 1) > 0.95, "busy link at {}", of(2, 1));
+    assert_eq!(of(3, 1), 0.0);
+    assert_eq!(of(1, 2), 0.0);
+}
+
+#[test]
+fn edge_cache_misses_once_then_hits() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    let miss = sim.send_cdn_request(100, 1, 7);
+    sim.run_simulation(f64::INFINITY);
+    let hit = sim.send_cdn_request(100, 1, 7);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!((sim.cache_misses, sim.cache_hits), (1, 1));
+    let (miss, hit) = (sim.ttfb(miss).expect("miss answered"), sim.ttfb(hit).expect("hit answered"));
+    // The miss pays a Johannesburg -> New York round trip on top
+    assert!(miss > hit + 0.05, "miss {} vs hit {}", miss, hit);
+}
+
+#[test]
+fn run_summary_matches_recorded_results() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    for (from, to) in [(100, 5), (101, 2), (5, 100), (2, 101)] {
+        sim.send_packet_ex(from, to, 1500, PacketType::Standard);
+    }
+    let summary = sim.run_simulation(f64::INFINITY);
+    let latencies: Vec<f64> = sim.completed_packets.iter().map(|(_, l)| *l).collect();
+    assert_eq!(summary.delivered, 4);
+    assert_eq!(summary.delivered, latencies.len());
+    assert_eq!(summary.dropped, sim.dropped_packets.len());
+    assert!((summary.avg_latency - latencies.iter().sum::<f64>() / 4.0).abs() < 1e-12);
+    assert_eq!(summary.max_latency, latencies.iter().cloned().fold(0.0, f64::max));
+    assert_eq!(summary.p99_latency, sim.latency_percentile(99.0, 0.0));
+    assert_eq!(summary.duration, sim.current_time);
+}
+
+/// SYN latency when it is sent right behind a 10MB bulk packet on one link.
+fn syn_behind_bulk(scheduler: LinkScheduler) -> (f64, f64) {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.set_link_scheduler(2, 1, scheduler);
+    sim.send_packet_ex(2, 1, 10_000_000, PacketType::Standard);
+    sim.send_packet_ex(2, 1, 64, PacketType::TcpSyn);
+    sim.run_simulation(f64::INFINITY);
+    let syn = sim.completed_of_type(PacketType::TcpSyn).map(|(_, l)| *l).next().expect("SYN delivered");
+    let bulk_serialization = 10_000_000.0 * 8.0 / sim.get_link(2, 1).unwrap().bandwidth;
+    (syn, bulk_serialization)
+}
+
+#[test]
+fn strict_priority_lets_syn_skip_queued_bulk() {
+    let (fifo, bulk) = syn_behind_bulk(LinkScheduler::Fifo);
+    let (priority, _) = syn_behind_bulk(LinkScheduler::StrictPriority);
+    assert!(fifo > bulk, "FIFO SYN waited {} for {} of bulk", fifo, bulk);
+    assert!(priority < fifo - 0.9 * bulk, "priority SYN took {}", priority);
+}
+
+#[test]
+fn nodes_are_found_by_unique_name() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.add_client(Client {
+        id: 102,
+        location: GeoLocation { latitude: -33.9, longitude: 18.4, name: "Cape Town".to_string() },
+        region: "Africa".to_string(),
+    });
+    assert_eq!(sim.node_id_by_name("Johannesburg"), Some(1));
+    assert_eq!(sim.node_id_by_name("New York"), Some(5));
+    assert_eq!(sim.node_id_by_name("Pretoria"), Some(100));
+    assert_eq!(sim.node_id_by_name("Cape Town"), None);
+    assert_eq!(sim.node_id_by_name("Nairobi"), None);
+    let mut ids: Vec<usize> = sim.all_nodes().map(|(id, _)| id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![1, 2, 5, 100, 101, 102]);
+}
+
+#[test]
+fn burst_packets_get_distinct_ids() {
+    let mut sim = two_hop();
+    for _ in 0..10 {
+        sim.send_packet_ex(2, 3, 500, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let ids: HashSet<usize> = sim.completed_packets.iter().map(|(p, _)| p.id).collect();
+    assert_eq!(sim.completed_packets.len(), 10);
+    assert_eq!(ids.len(), 10);
+}
+
+#[test]
+fn batch_sends_are_created_at_their_offsets() {
+    let mut sim = two_hop();
+    let offsets = [0.0, 0.01, 0.025, 0.1];
+    let batch: Vec<SendSpec> = offsets.iter().map(|&offset| (2, 3, 500, PacketType::Standard, offset)).collect();
+    sim.send_batch(&batch);
+    sim.run_simulation(f64::INFINITY);
+    let mut created: Vec<f64> = sim.completed_packets.iter().map(|(p, _)| p.created_at).collect();
+    created.sort_by(f64::total_cmp);
+    assert_eq!(created.len(), offsets.len());
+    for (created_at, offset) in created.iter().zip(offsets) {
+        assert!((created_at - offset).abs() < 1e-12, "created at {} for offset {}", created_at, offset);
+    }
+}
+
+#[test]
+fn waypoint_cable_length_is_the_sum_of_its_legs() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    let lagos = GeoLocation { latitude: 6.4541, longitude: 3.3947, name: "Lagos".to_string() };
+    sim.connect_nodes_via(101, 2, std::slice::from_ref(&lagos), 10_000_000_000.0);
+    let cable = sim.links.last().unwrap();
+    let (cape_town, london) = (sim.location_of(101).unwrap(), sim.location_of(2).unwrap());
+    let legs = cape_town.distance_with(&lagos, &sim.physics) + lagos.distance_with(london, &sim.physics);
+    assert!((cable.distance - legs).abs() < 1.0);
+    assert!(cable.distance > sim.calculate_distance(101, 2));
+}
+
+#[test]
+fn peak_window_slows_transmission_tenfold() {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    for link in sim.links.iter_mut() {
+        link.latency = 0.0;
+        link.bandwidth = 1_000_000_000.0;
+    }
+    sim.set_bandwidth_schedule(2, 1, vec![(0.1, 100_000_000.0), (0.3, 1_000_000_000.0)]);
+    let probes = [0.05, 0.15, 0.4];
+    let batch: Vec<SendSpec> = probes.iter().map(|&offset| (2, 1, 1_000_000, PacketType::Standard, offset)).collect();
+    sim.send_batch(&batch);
+    sim.run_simulation(f64::INFINITY);
+    let latency_at = |offset: f64| {
+        sim.completed_packets.iter().find(|(p, _)| (p.created_at - offset).abs() < 1e-9).map(|(_, l)| *l).expect("delivered")
+    };
+    let (before, peak, after) = (latency_at(0.05), latency_at(0.15), latency_at(0.4));
+    assert!((before - after).abs() < 1e-9);
+    assert!(peak > 8.0 * before && peak < 12.0 * before, "peak {} vs off-peak {}", peak, before);
+}
+
+#[test]
+fn json_export_lists_packets_and_aggregate_latency() {
+    let mut sim = two_hop();
+    for _ in 0..3 {
+        sim.send_packet_ex(2, 3, 1000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let json = sim.export_results_json();
+    let packets = &json[json.find("\"packets\": [").expect("packets array")..];
+    assert_eq!(packets.matches("\"id\":").count(), 3);
+    let aggregate = &json[json.find("\"aggregate\": {").expect("aggregate object")..json.find('}').unwrap()];
+    let avg: f64 = aggregate.lines()
+        .find_map(|line| line.trim().strip_prefix("\"avg_latency_ms\": "))
+        .map(|value| value.trim_end_matches(',').parse().expect("numeric average"))
+        .expect("avg_latency_ms present");
+    assert!(avg > 0.0);
+}
+
+#[test]
+fn achieved_latency_exceeds_vacuum_floor() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.add_server(Server {
+        id: 3,
+        location: GeoLocation { latitude: 35.7720, longitude: 140.3929, name: "Tokyo Narita".to_string() },
+        processing_delay: 0.0005,
+        service_time_distribution: None,
+        bandwidth: 100_000_000_000.0,
+        cache: HashSet::new(),
+        role: ServerRole::Origin,
+        origin: None,
+        as_number: 2516,
+        power_watts: 1_500.0,
+        region: "Asia".to_string(),
+    });
+    sim.connect_nodes(1, 3, 10_000_000_000.0);
+    sim.connect_nodes(3, 1, 10_000_000_000.0);
+    sim.send_packet_ex(1, 3, 1500, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    let (_, achieved) = sim.completed_for_flow(1, 3).next().expect("delivered");
+    let floor = sim.theoretical_min_latency(1, 3);
+    let vacuum = sim.calculate_distance(1, 3) / SPEED_OF_LIGHT;
+    assert!((floor - vacuum).abs() < 1e-9);
+    assert!(*achieved > floor, "achieved {} vs floor {}", achieved, floor);
+}
+
+/// Combined bits per second carried both ways over a 100 Mbps 1 <-> 2 pair.
+fn bidirectional_throughput(duplex: Duplex) -> f64 {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    for link in sim.links.iter_mut() {
+        link.latency = 0.0;
+        link.bandwidth = 100_000_000.0;
+    }
+    sim.set_link_duplex(1, 2, duplex);
+    for _ in 0..10 {
+        sim.send_packet_ex(1, 2, 1_000_000, PacketType::Standard);
+        sim.send_packet_ex(2, 1, 1_000_000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 20);
+    20.0 * 1_000_000.0 * 8.0 / sim.current_time
+}
+
+#[test]
+fn half_duplex_caps_combined_throughput_at_one_channel() {
+    let full = bidirectional_throughput(Duplex::Full);
+    let half = bidirectional_throughput(Duplex::Half);
+    assert!(full > 1.8 * 100_000_000.0, "full duplex carried {} bps", full);
+    assert!(half <= 100_000_000.0 * (1.0 + 1e-9), "half duplex carried {} bps", half);
+}
+
+#[test]
+fn retransmission_raises_delivered_count_on_lossy_link() {
+    let delivered = |retransmit: Option<RetransmitPolicy>| {
+        let mut sim = two_hop();
+        sim.retransmit = retransmit;
+        sim.set_link_loss(1, 3, 0.2);
+        for _ in 0..100 {
+            sim.send_packet_ex(2, 3, 1000, PacketType::Standard);
+        }
+        sim.run_simulation(f64::INFINITY);
+        (sim.completed_packets.len(), sim.retransmissions)
+    };
+    let (without, no_retransmissions) = delivered(None);
+    let (with, retransmissions) = delivered(Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 8 }));
+    assert!(with > without, "{} delivered with retransmission vs {} without", with, without);
+    assert_eq!(no_retransmissions, 0);
+    assert!(retransmissions > 0);
+}
+
+#[test]
+fn ecn_marking_rises_with_offered_load() {
+    let marked_fraction = |load: f64| {
+        let mut sim = topogen::star(2);
+        sim.verbose = false;
+        sim.set_link_ecn(2, 1, Some(30_000));
+        let bandwidth = 10_000_000.0;
+        sim.links.iter_mut().for_each(|l| l.bandwidth = bandwidth);
+        sim.generate_poisson_traffic(2, 1, load * bandwidth / (1500.0 * 8.0), 2.0, &PacketSizeDistribution::Fixed(1500));
+        sim.run_simulation(f64::INFINITY);
+        assert!(sim.dropped_packets.is_empty());
+        let marked = sim.completed_packets.iter().filter(|(p, _)| p.ecn_marked).count();
+        marked as f64 / sim.completed_packets.len() as f64
+    };
+    let fractions: Vec<f64> = [0.3, 0.9, 0.99].into_iter().map(marked_fraction).collect();
+    assert_eq!(fractions[0], 0.0);
+    assert!(fractions[1] < fractions[2], "marking did not rise: {:?}", fractions);
+    assert!(fractions[2] > 0.0);
+}
+
+#[test]
+fn sweep_matches_sequential_runs() {
+    let configs: Vec<SimConfig> = [500.0, 1000.0, 2000.0, 4000.0].into_iter().enumerate()
+        .map(|(i, rate_pps)| SimConfig {
+            seed: DEFAULT_SEED + i as u64,
+            duration: 1.0,
+            setup: Arc::new(move |sim: &mut NetworkSimulation| {
+                let seed = sim.seed;
+                *sim = two_hop();
+                sim.set_seed(seed);
+                sim.generate_poisson_traffic(2, 3, rate_pps, 0.5, &PacketSizeDistribution::Fixed(1500));
+            }),
+        })
+        .collect();
+    let sequential: Vec<RunSummary> = configs.iter().map(SimConfig::run).collect();
+    let parallel = run_sweep(configs);
+    assert_eq!(parallel, sequential);
+    assert!(parallel.windows(2).all(|pair| pair[0].delivered < pair[1].delivered));
+}
+
+#[test]
+fn trace_components_sum_to_end_to_end_latency() {
+    let mut sim = two_hop();
+    let trace = sim.trace_packet(2, 3, 1000, PacketType::Standard).expect("route exists");
+    assert_eq!(trace.hops.iter().map(|h| (h.from, h.to)).collect::<Vec<_>>(), vec![(2, 1), (1, 3)]);
+    assert!(trace.hops[1].processing > 0.0);
+    let components: f64 = trace.hops.iter()
+        .map(|h| h.processing + h.queuing + h.transmission + h.propagation)
+        .sum();
+    assert!((components - trace.total_latency).abs() < 1e-12);
+
+    sim.send_packet_ex(2, 3, 1000, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert!((sim.completed_packets[0].1 - trace.total_latency).abs() < 1e-12);
+}
+
+#[test]
+fn source_route_is_followed_exactly() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.connect_nodes(101, 2, 10_000_000_000.0);
+    assert_eq!(sim.find_next_hop(1, 2), Some(2));
+    let route = vec![100, 1, 101, 2];
+    sim.send_packet_routed(&route, 1500, PacketType::Standard);
+    sim.send_packet_routed(&[100, 101, 2], 1500, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert_eq!(sim.completed_packets[0].0.path, route);
+    assert_eq!(sim.dropped_packets.len(), 1);
+    assert_eq!(sim.dropped_packets[0].1, DropReason::InvalidSourceRoute);
+}
+
+#[test]
+fn long_cable_is_propagation_bound_and_big_packet_serialization_bound() {
+    let sim = crate::theories::terrestrial_topology();
+    let (propagation, serialization) = sim.link_delay_breakdown(1, 2, 64).expect("subsea link");
+    assert!(propagation > 1000.0 * serialization, "subsea: {} vs {}", propagation, serialization);
+    let (propagation, serialization) = sim.link_delay_breakdown(100, 1, 10_000_000).expect("access link");
+    assert!(serialization > 100.0 * propagation, "access: {} vs {}", propagation, serialization);
+}
+
+/// Drop rate per 40ms window of 2x overload into a 10 Mbps link with a 200KB buffer.
+fn overload_drop_rates(red: Option<RedConfig>) -> Vec<f64> {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.bandwidth = 10_000_000.0);
+    sim.set_link_queue_capacity(2, 1, Some(200_000));
+    sim.set_link_red(2, 1, red);
+    sim.generate_poisson_traffic(2, 1, 2.0 * 10_000_000.0 / (1500.0 * 8.0), 0.4, &PacketSizeDistribution::Fixed(1500));
+    sim.run_simulation(f64::INFINITY);
+    (0..10)
+        .map(|w| {
+            let in_window = |p: &DataPacket| p.created_at >= w as f64 * 0.04 && p.created_at < (w + 1) as f64 * 0.04;
+            let dropped = sim.dropped_packets.iter().filter(|(p, _)| in_window(p)).count();
+            let delivered = sim.completed_packets.iter().filter(|(p, _)| in_window(p)).count();
+            dropped as f64 / (dropped + delivered) as f64
+        })
+        .collect()
+}
+
+#[test]
+fn red_drops_rise_gradually_unlike_tail_drop() {
+    let tail = overload_drop_rates(None);
+    let red = overload_drop_rates(Some(RedConfig { min_bytes: 50_000, max_bytes: 200_000, max_drop_prob: 0.1 }));
+    let onset = |rates: &[f64]| rates.iter().position(|&r| r > 0.0).expect("overload drops packets");
+    let (tail_onset, red_onset) = (onset(&tail), onset(&red));
+    assert!(red_onset < tail_onset, "RED {:?} vs tail drop {:?}", red, tail);
+    // RED starts with a trickle; tail drop goes from nothing to a large share at once
+    assert!(red[red_onset] < 0.1 && red[red_onset + 1] >= red[red_onset]);
+    assert!(tail[tail_onset] > 0.3);
+}
+
+#[test]
+fn anycast_delivers_to_nearest_replica() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    assert_eq!(sim.send_anycast(100, &[2, 1], 1500, PacketType::Standard), Some(1));
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1);
+    assert_eq!(sim.completed_packets[0].0.destination_id, 1);
+    assert_eq!(sim.completed_packets[0].0.path, vec![100, 1]);
+}
+
+#[test]
+fn unreachable_destination_records_one_no_route_drop() {
+    let mut sim = two_hop();
+    sim.links.retain(|l| l.to != 3);
+    assert!(!sim.send_packet_ex(2, 3, 1000, PacketType::Standard));
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.completed_packets.is_empty());
+    assert_eq!(sim.dropped_packets.len(), 1);
+    assert_eq!(sim.dropped_packets[0].1, DropReason::NoRoute);
+}
+
+#[test]
+fn diameter_is_the_farthest_pair_latency() {
+    let sim = crate::theories::terrestrial_topology();
+    let nodes = sim.node_order();
+    let pairs: Vec<(usize, usize, f64)> = nodes.iter()
+        .flat_map(|&a| nodes.iter().map(move |&b| (a, b)))
+        .filter(|(a, b)| a != b)
+        .filter_map(|(a, b)| Some((a, b, sim.path_latency(a, b)?)))
+        .collect();
+    let (from, to, farthest) = pairs.iter().cloned().fold((0, 0, 0.0), |best, pair| if pair.2 > best.2 { pair } else { best });
+    let diameter = sim.network_diameter();
+    assert!(diameter.is_finite() && diameter > 0.0);
+    assert_eq!(diameter, farthest);
+    // A South African client reaching the far side of the Atlantic
+    let (client, server) = if from >= 100 { (from, to) } else { (to, from) };
+    assert!([100, 101].contains(&client) && [2, 5].contains(&server), "farthest pair {} -> {}", from, to);
+    let average = sim.average_path_latency();
+    assert!(average > 0.0 && average < diameter);
+}
+
+#[test]
+fn multicast_crosses_shared_trunk_once() {
+    let mut sim = topogen::star(5);
+    sim.verbose = false;
+    // Leaf 2's only link is the trunk every branch shares
+    assert_eq!(sim.send_multicast(2, &[3, 4, 5], 1000, PacketType::Standard), 4);
+    sim.run_simulation(f64::INFINITY);
+    let delivered: HashSet<usize> = sim.completed_packets.iter().map(|(p, _)| p.destination_id).collect();
+    assert_eq!(delivered, HashSet::from([3, 4, 5]));
+    assert_eq!(sim.get_link(2, 1).unwrap().bytes_sent, 1000);
+    for leaf in 3..=5 {
+        assert_eq!(sim.get_link(1, leaf).unwrap().bytes_sent, 1000);
+    }
+}
+
+#[test]
+fn jitter_reorders_and_steady_link_does_not() {
+    let reordering = |jitter: f64| {
+        let mut sim = topogen::star(2);
+        sim.verbose = false;
+        sim.set_link_jitter(2, 1, jitter);
+        let batch: Vec<SendSpec> = (0..200).map(|i| (2, 1, 1500, PacketType::Standard, i as f64 * 0.000_05)).collect();
+        sim.send_batch(&batch);
+        sim.run_simulation(f64::INFINITY);
+        sim.reordering_stats(Some((2, 1)), 0.0)
+    };
+    let steady = reordering(0.0);
+    assert_eq!(steady.delivered, 200);
+    assert_eq!((steady.out_of_order, steady.max_extent), (0, 0));
+    let jittery = reordering(0.001);
+    assert!(jittery.out_of_order > 0 && jittery.max_extent > 0);
+}
+
+#[test]
+fn utilization_aware_routing_shifts_off_saturated_link() {
+    let mut sim = topogen::full_mesh(3);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.bandwidth = 10_000_000.0);
+    sim.set_routing_mode(RoutingMode::UtilizationAware { refresh_interval: 0.02 });
+    sim.generate_poisson_traffic(2, 3, 1.2 * 10_000_000.0 / (1500.0 * 8.0), 0.2, &PacketSizeDistribution::Fixed(1500));
+    sim.run_simulation(f64::INFINITY);
+    let detoured = |from: f64, to: f64| sim.completed_for_flow(2, 3)
+        .filter(|(p, _)| p.created_at >= from && p.created_at < to && p.path.len() > 2)
+        .count();
+    // Routes computed on the idle network take the direct cable
+    assert_eq!(detoured(0.0, 0.02), 0);
+    assert!(detoured(0.02, 0.2) > 0);
+}
+
+#[test]
+fn queue_delay_drains_as_time_advances() {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.bandwidth = 10_000_000.0);
+    for _ in 0..50 {
+        sim.send_packet_ex(2, 1, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(0.01);
+    let early = sim.link_queue_delay(2, 1).expect("link exists");
+    sim.run_simulation(0.03);
+    let later = sim.link_queue_delay(2, 1).expect("link exists");
+    assert!(early > 0.0, "no backlog after the burst");
+    assert!(later < early, "queue delay {} then {}", early, later);
+    assert_eq!(sim.link_queue_delay(2, 3), None);
+}
+
+/// Triangle 1-2-3 with 50ms cables, so packets spend a while on the wire.
+fn slow_triangle() -> NetworkSimulation {
+    let mut sim = topogen::full_mesh(3);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.latency = 0.05);
+    sim
+}
+
+#[test]
+fn maintenance_reroutes_without_dropping() {
+    let mut sim = slow_triangle();
+    sim.schedule_maintenance(2, 3, 0.01, 0.03);
+    let batch: Vec<SendSpec> = (0..20).map(|i| (2, 3, 1500, PacketType::Standard, i as f64 * 0.002)).collect();
+    sim.send_batch(&batch);
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.dropped_packets.is_empty(), "{:?}", sim.drop_breakdown());
+    assert_eq!(sim.completed_packets.len(), 20);
+    for (packet, _) in &sim.completed_packets {
+        let draining = packet.created_at >= 0.01 && packet.created_at < 0.03;
+        assert_eq!(packet.path.len() > 2, draining, "packet created at {} took {:?}", packet.created_at, packet.path);
+    }
+}
+
+#[test]
+fn link_failure_drops_packets_in_flight() {
+    let mut sim = slow_triangle();
+    for _ in 0..10 {
+        sim.send_packet_ex(2, 3, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(0.02);
+    sim.set_link_down(2, 3);
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.completed_packets.is_empty());
+    assert_eq!(sim.dropped_packets.iter().filter(|(_, reason)| *reason == DropReason::LinkDown).count(), 10);
+}
+
+#[test]
+fn vincenty_stays_within_half_a_percent_of_haversine() {
+    let johannesburg = GeoLocation { latitude: -26.2041, longitude: 28.0473, name: "Johannesburg".to_string() };
+    let london = GeoLocation { latitude: 51.5074, longitude: -0.1278, name: "London".to_string() };
+    let (ellipsoid, sphere) = (johannesburg.vincenty_distance_to(&london), johannesburg.distance_to(&london));
+    let difference = (ellipsoid - sphere).abs() / sphere;
+    assert!(difference > 0.0 && difference < 0.005, "{:.4}% apart", difference * 100.0);
+
+    let antipode = GeoLocation { latitude: 0.5, longitude: 179.7, name: "Antipode".to_string() };
+    let origin = GeoLocation { latitude: 0.0, longitude: 0.0, name: "Origin".to_string() };
+    assert!(origin.vincenty_distance_to(&antipode).is_finite());
+}
+
+#[test]
+fn bundle_waits_for_next_contact_window() {
+    let sim = two_hop();
+    let always = sim.send_bundle(2, 3, 1_000_000, &ContactSchedule::default()).expect("connected");
+    assert!(always.total_custody_time() < 1e-12);
+
+    let mut schedule = ContactSchedule::default();
+    schedule.add_periodic(1, 3, 10.0, 2.0, 60.0, 600.0);
+    let bundle = sim.send_bundle(2, 3, 1_000_000, &schedule).expect("window fits the bundle");
+    assert_eq!(bundle.path, vec![2, 1, 3]);
+    assert!(bundle.delivered_at > 10.0);
+    // Held at the hub from shortly after sending until the window opens
+    let custody = bundle.total_custody_time();
+    assert!(custody > 10.0 - always.delivered_at && custody < 10.0, "custody {}", custody);
+    assert!((bundle.delivered_at - always.delivered_at - bundle.total_custody_time()).abs() < 1e-9);
+}
+
+#[test]
+fn in_flight_rises_on_send_and_falls_on_delivery() {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.latency = 0.01);
+    assert_eq!(sim.metrics_snapshot().in_flight, 0);
+    for _ in 0..5 {
+        sim.send_packet_ex(2, 1, 1000, PacketType::Standard);
+    }
+    let sent = sim.metrics_snapshot();
+    assert_eq!((sent.in_flight, sent.delivered), (5, 0));
+    sim.run_simulation(f64::INFINITY);
+    let done = sim.metrics_snapshot();
+    assert_eq!((done.in_flight, done.delivered, done.dropped), (0, 5, 0));
+    assert_eq!(done.queued_events, 0);
+    assert!(done.events_processed >= 5 && done.current_time >= 0.01);
+}
+
+#[test]
+fn link_latency_scales_with_path_inefficiency() {
+    let latency_with = |path_inefficiency_factor: f64| {
+        let mut sim = NetworkSimulation::with_config(PhysicsConfig { path_inefficiency_factor, ..PhysicsConfig::default() });
+        sim.verbose = false;
+        for server in topogen::star(2).servers.into_values() {
+            sim.add_server(server);
+        }
+        sim.connect_nodes(1, 2, 10_000_000_000.0);
+        sim.get_link(1, 2).unwrap().latency
+    };
+    let (base, doubled) = (latency_with(1.3), latency_with(2.6));
+    assert!(base > 0.0);
+    assert!((doubled / base - 2.0).abs() < 1e-9, "{} vs {}", doubled, base);
+}
+
+#[test]
+fn burst_peaks_in_flight_then_drains() {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.bandwidth = 1_000_000.0);
+    for _ in 0..20 {
+        sim.send_packet_ex(2, 1, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.peak_in_flight() >= 19, "peak {}", sim.peak_in_flight());
+    assert!(sim.peak_in_flight() <= 20);
+    assert_eq!(sim.metrics_snapshot().in_flight, 0);
+}
+
+#[test]
+fn astar_matches_dijkstra_with_less_exploration() {
+    let sim = crate::theories::terrestrial_topology();
+    let mut saved = 0;
+    for (from, to) in [(101, 2), (100, 5), (2, 100)] {
+        assert_eq!(sim.find_path_astar(from, to), sim.find_path_dijkstra(from, to));
+        let (dijkstra, astar) = sim.search_effort(from, to);
+        assert!(astar <= dijkstra, "{} -> {}: A* expanded {} vs {}", from, to, astar, dijkstra);
+        saved += dijkstra - astar;
+    }
+    assert!(saved > 0);
+}
+
+#[test]
+fn handshake_and_cdn_traffic_break_down_by_type() {
+    use crate::theories::TheoryTests;
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    TheoryTests::demonstrate_tcp_handshake(&mut sim, 100, 5);
+    TheoryTests::demonstrate_tcp_handshake(&mut sim, 101, 2);
+    sim.run_simulation(f64::INFINITY);
+    TheoryTests::demonstrate_cdn_solution(&mut sim, 100, 5, 1);
+    sim.run_simulation(f64::INFINITY);
+
+    let by_type = sim.latency_by_type(0.0);
+    for p_type in [PacketType::TcpSyn, PacketType::TcpSynAck, PacketType::TcpAck] {
+        assert_eq!(by_type[&p_type].count, 2, "{:?}", p_type);
+    }
+    // Origin, cold edge and warm edge answers, plus the origin's answer to the cold edge
+    assert_eq!(by_type[&PacketType::CdnResponse].count, 4);
+    assert_eq!(by_type.values().map(|t| t.count).sum::<usize>(), sim.completed_packets.len());
+    assert!(by_type.values().all(|t| t.avg_latency > 0.0 && t.p95_latency > 0.0));
+}
+
+#[test]
+fn tenfold_oversubscribed_aggregation_node_is_flagged() {
+    let mut sim = topogen::star(12);
+    sim.verbose = false;
+    sim.links.clear();
+    // Ten 1 Gbps access links feed hub 1, which has a single 1 Gbps uplink to 2
+    for leaf in 3..=12 {
+        sim.connect_nodes(leaf, 1, 1_000_000_000.0);
+    }
+    sim.connect_nodes(1, 2, 1_000_000_000.0);
+    let warnings = sim.check_oversubscription();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].node, 1);
+    assert!((warnings[0].ratio - 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn swapping_strategy_changes_the_route() {
+    let mut sim = slow_triangle();
+    // A geostationary hop: one hop, but far slower than the two-hop fiber detour
+    if let Some(satellite) = sim.links.iter_mut().find(|l| l.from == 2 && l.to == 3) {
+        satellite.latency = 0.24;
+    }
+    sim.set_routing_strategy(Box::new(BfsRouting));
+    assert_eq!(sim.route_next_hop(2, 3), Some(3));
+    sim.set_routing_strategy(Box::new(DijkstraRouting));
+    assert_eq!(sim.route_next_hop(2, 3), Some(1));
+    sim.send_packet_ex(2, 3, 1500, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets[0].0.path, vec![2, 1, 3]);
+}
+
+#[test]
+fn drop_breakdown_attributes_each_reason() {
+    let mut sim = topogen::star(4);
+    sim.verbose = false;
+    sim.links.retain(|l| l.to != 4);
+    sim.set_link_queue_capacity(2, 1, Some(3_000));
+    sim.set_link_loss(1, 3, 1.0);
+    // Leaf to leaf takes two hops, so nothing gets past the hub
+    sim.hop_limit = Some(1);
+    // Two 1500-byte packets fill the queue; the rest of the burst is tail dropped
+    for _ in 0..10 {
+        sim.send_packet_ex(2, 3, 1500, PacketType::Standard);
+    }
+    sim.send_packet_ex(1, 3, 1500, PacketType::Standard);
+    sim.send_packet_ex(3, 4, 1500, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    let breakdown = sim.drop_breakdown();
+    assert_eq!(breakdown.get(&DropReason::QueueFull), Some(&8));
+    assert_eq!(breakdown.get(&DropReason::HopLimitExceeded), Some(&2));
+    assert_eq!(breakdown.get(&DropReason::LinkLoss), Some(&1));
+    assert_eq!(breakdown.get(&DropReason::NoRoute), Some(&1));
+    assert_eq!(breakdown.values().sum::<usize>(), sim.dropped_packets.len());
+    assert!(sim.completed_packets.is_empty());
+}
+
+#[test]
+fn hop_limit_stops_a_routing_loop() {
+    let mut sim = topogen::ring(4);
+    sim.verbose = false;
+    sim.hop_limit = Some(8);
+    // 1 and 2 each send traffic for 3 to the other
+    sim.route_cache.insert((1, 3), Some(2));
+    sim.route_cache.insert((2, 3), Some(1));
+    sim.use_precomputed_routes = true;
+    sim.send_packet_ex(1, 3, 1500, PacketType::Standard);
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.completed_packets.is_empty());
+    let (packet, reason) = &sim.dropped_packets[0];
+    assert_eq!(*reason, DropReason::HopLimitExceeded);
+    assert_eq!(packet.path.len(), 9);
+}
+
+#[test]
+fn weighted_uplinks_split_flows_two_to_one() {
+    let mut sim = topogen::star(15);
+    sim.verbose = false;
+    sim.links.clear();
+    // Hub 1 reaches 2 directly or via 3; leaves 4..=15 are customers behind the hub
+    sim.connect_nodes(1, 2, 10_000_000_000.0);
+    sim.connect_nodes(1, 3, 10_000_000_000.0);
+    sim.connect_nodes(3, 2, 10_000_000_000.0);
+    for customer in 4..=15 {
+        sim.connect_nodes(customer, 1, 1_000_000_000.0);
+    }
+    sim.set_uplink_weights(1, vec![(2, 2), (3, 1)]);
+    for _ in 0..3 {
+        for customer in 4..=15 {
+            sim.send_packet_ex(customer, 2, 1500, PacketType::Standard);
+        }
+    }
+    sim.run_simulation(f64::INFINITY);
+    let (mut direct, mut detour) = (HashSet::new(), HashSet::new());
+    for (packet, _) in &sim.completed_packets {
+        if packet.path.contains(&3) { detour.insert(packet.source_id) } else { direct.insert(packet.source_id) };
+    }
+    assert_eq!((direct.len(), detour.len()), (8, 4));
+    assert!(direct.is_disjoint(&detour), "a flow switched uplinks");
+}
+
+#[test]
+fn only_edge_servers_answer_from_cache() {
+    let ttfbs = |role: ServerRole| {
+        let mut sim = crate::theories::terrestrial_topology();
+        sim.verbose = false;
+        sim.servers.get_mut(&1).unwrap().role = role;
+        let first = sim.send_cdn_request(100, 1, 7);
+        sim.run_simulation(f64::INFINITY);
+        let second = sim.send_cdn_request(100, 1, 7);
+        sim.run_simulation(f64::INFINITY);
+        (sim.ttfb(first).unwrap(), sim.ttfb(second).unwrap(), sim.cache_hits)
+    };
+    let (origin_first, origin_second, origin_hits) = ttfbs(ServerRole::Origin);
+    assert_eq!(origin_hits, 0);
+    assert!((origin_first - origin_second).abs() < 1e-9);
+    let (edge_first, edge_second, edge_hits) = ttfbs(ServerRole::Edge);
+    assert_eq!(edge_hits, 1);
+    assert!(edge_first > origin_first && (edge_second - origin_second).abs() < 1e-9);
+}
+
+#[test]
+fn local_flow_meets_sla_and_intercontinental_misses_it() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    for _ in 0..20 {
+        sim.send_packet_ex(100, 1, 1500, PacketType::Standard);
+        sim.send_packet_ex(100, 5, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let worst = |to: usize| sim.completed_for_flow(100, to).map(|(_, l)| l * 1000.0).fold(0.0, f64::max);
+
+    let local = sim.check_sla(100, 1, 10.0);
+    assert!(local.passed);
+    assert_eq!(local.samples, 20);
+    assert!(local.measured_p99_ms > 0.0 && local.measured_p99_ms <= worst(1));
+    assert!((local.margin_ms - (10.0 - local.measured_p99_ms)).abs() < 1e-12);
+
+    let far = sim.check_sla(100, 5, 10.0);
+    assert!(!far.passed);
+    assert!(far.measured_p99_ms > 50.0 && far.measured_p99_ms <= worst(5));
+    assert!(far.margin_ms < 0.0);
+}
+
+#[test]
+fn trace_fixture_is_injected_and_delivered_on_schedule() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("traces/cape_town_incident.csv");
+    let trace = crate::trace::load_traffic_trace(path).expect("fixture readable");
+    assert_eq!((trace.records.len(), trace.skipped_lines), (11, 2));
+
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    assert_eq!(sim.inject_trace(&trace.records), 11);
+    sim.run_simulation(f64::INFINITY);
+    let mut delivered: Vec<&DataPacket> = sim.completed_packets.iter().map(|(p, _)| p).collect();
+    assert_eq!(delivered.len(), 11);
+    delivered.sort_by_key(|p| p.id);
+    for (packet, &(time, src, dst, size)) in delivered.iter().zip(&trace.records) {
+        assert_eq!((packet.source_id, packet.destination_id, packet.size_bytes), (src, dst, size));
+        assert!((packet.created_at - time).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn handshake_setup_takes_one_and_a_half_round_trips() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    crate::theories::TheoryTests::demonstrate_tcp_handshake(&mut sim, 100, 5);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.connections.len(), 1);
+    let connection = sim.connections[0];
+    assert_eq!((connection.client, connection.server), (100, 5));
+    assert!((connection.established_at - connection.syn_sent_at - connection.setup_time).abs() < 1e-12);
+    let expected = 1.5 * sim.path_rtt(100, 5).unwrap();
+    // Processing and serialization add a little on top of propagation
+    assert!(connection.setup_time >= expected && connection.setup_time < expected * 1.02,
+            "setup {} vs 1.5 RTT {}", connection.setup_time, expected);
+}
+
+#[test]
+fn per_region_delivered_counts_sum_to_total() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.servers.get_mut(&2).unwrap().region = "UK".to_string();
+    for (from, to) in [(100, 1), (101, 1), (100, 5), (101, 2), (2, 101), (5, 2)] {
+        sim.send_packet_ex(from, to, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let counts = sim.packets_delivered_by_region(0.0);
+    let expected: BTreeMap<String, usize> = [("Africa", 3), ("North America", 1), ("UK", 2)]
+        .into_iter().map(|(region, count)| (region.to_string(), count)).collect();
+    assert_eq!(counts, expected);
+    assert_eq!(counts.values().sum::<usize>(), sim.completed_packets.len());
+    let latency = sim.avg_latency_by_region(0.0);
+    assert!(latency["UK"] > latency["Africa"]);
+}
+
+#[test]
+fn interpolation_runs_between_the_endpoints() {
+    let johannesburg = GeoLocation { latitude: -26.2041, longitude: 28.0473, name: "Johannesburg".to_string() };
+    let london = GeoLocation { latitude: 51.5074, longitude: -0.1278, name: "London".to_string() };
+    let close = |a: &GeoLocation, b: &GeoLocation| (a.latitude - b.latitude).abs() < 1e-9 && (a.longitude - b.longitude).abs() < 1e-9;
+    assert!(close(&johannesburg.interpolate(&london, 0.0), &johannesburg));
+    assert!(close(&johannesburg.interpolate(&london, 1.0), &london));
+    let midpoint = johannesburg.interpolate(&london, 0.5);
+    assert!(midpoint.latitude > johannesburg.latitude && midpoint.latitude < london.latitude);
+    assert!(midpoint.longitude < johannesburg.longitude && midpoint.longitude > london.longitude);
+    assert!((midpoint.distance_to(&johannesburg) - midpoint.distance_to(&london)).abs() < 1.0);
+}
+
+#[test]
+fn in_flight_packet_is_located_on_its_link() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    let id = sim.next_packet_id;
+    sim.send_packet_ex(1, 5, 1500, PacketType::Standard);
+    let halfway = sim.get_link(1, 5).unwrap().latency / 2.0;
+    sim.run_simulation(halfway);
+    let position = sim.packet_position(id, halfway).expect("packet in flight");
+    assert!(position.latitude > -26.2041 && position.latitude < 40.7128);
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.packet_position(id, sim.current_time).is_none());
+}
+
+#[test]
+fn hundred_packet_flow_completes_after_single_packet_flow() {
+    let mut sim = two_hop();
+    sim.links.iter_mut().for_each(|l| l.bandwidth = 100_000_000.0);
+    sim.send_flow(1, 2, 3, 1500, 1500);
+    sim.send_flow(2, 2, 3, 150_000, 1500);
+    sim.run_simulation(f64::INFINITY);
+    let flows = sim.flow_completion_times();
+    assert_eq!(flows.iter().map(|f| (f.flow_id, f.packets)).collect::<Vec<_>>(), vec![(1, 1), (2, 100)]);
+    assert!(flows[1].fct > flows[0].fct);
+    let (small, large) = sim.fct_distribution(100_000);
+    assert_eq!((small.flows, large.flows), (1, 1));
+    assert!(large.avg_fct > small.avg_fct);
+}
+
+#[test]
+fn removing_hub_prunes_links_and_drops_packets_headed_for_it() {
+    let mut sim = slow_triangle();
+    sim.update_server_bandwidth(1, 1_000_000_000.0);
+    assert_eq!(sim.servers[&1].bandwidth, 1_000_000_000.0);
+    for _ in 0..5 {
+        sim.send_packet_ex(2, 1, 1500, PacketType::Standard);
+        sim.send_packet_ex(3, 1, 1500, PacketType::Standard);
+    }
+    sim.run_simulation(0.02);
+    assert!(sim.remove_server(1).is_some());
+    assert!(sim.links.iter().all(|l| l.from != 1 && l.to != 1));
+    assert_eq!(sim.links.len(), 2);
+    sim.run_simulation(f64::INFINITY);
+    assert!(sim.completed_packets.is_empty());
+    assert_eq!(sim.drop_breakdown().get(&DropReason::NodeRemoved), Some(&10));
+    assert!(sim.remove_server(1).is_none());
+}
+
+#[test]
+fn direct_cable_candidate_beats_coastal_baseline() {
+    let mut baseline = crate::theories::terrestrial_topology();
+    baseline.verbose = false;
+    let mut candidate = crate::theories::terrestrial_topology();
+    candidate.verbose = false;
+    // Replace the West Africa coastal cable with one along the great circle
+    candidate.links.retain(|l| !(l.from == 1 && l.to == 2));
+    candidate.connect_nodes(1, 2, 10_000_000_000.0);
+    let straight = candidate.calculate_distance(1, 2) / candidate.physics.speed_in_fiber;
+    candidate.links.last_mut().unwrap().latency = straight;
+
+    let workload: Vec<SendSpec> = (0..50).map(|i| (1, 2, 1500, PacketType::Standard, i as f64 * 0.001)).collect();
+    let report = compare(&mut baseline, &mut candidate, &workload);
+    assert_eq!((report.baseline.delivered, report.candidate.delivered), (50, 50));
+    assert!(report.avg_latency_delta < 0.0 && report.p99_latency_delta < 0.0);
+    assert!((report.avg_latency_delta - (report.candidate.avg_latency - report.baseline.avg_latency)).abs() < 1e-12);
+    assert_eq!(report.drop_rate_delta, 0.0);
+}
+
+/// When a 200-byte header and a 300-byte body written back to back have both
+/// reached the server, over a 10ms link.
+fn write_write_completion(nagle: bool, delayed_ack: Option<f64>) -> f64 {
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().for_each(|l| l.latency = 0.01);
+    sim.set_tcp_options(2, 1, TcpOptions { nagle, delayed_ack });
+    sim.send_segment(2, 1, 200);
+    sim.send_segment(2, 1, 300);
+    sim.run_simulation(f64::INFINITY);
+    sim.completed_of_type(PacketType::Standard)
+        .filter(|(p, _)| p.destination_id == 1)
+        .map(|(p, latency)| p.created_at + latency)
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn nagle_with_delayed_ack_stalls_until_the_ack_timer() {
+    let plain = write_write_completion(false, None);
+    let nagle = write_write_completion(true, None);
+    let both = write_write_completion(true, Some(DELAYED_ACK_TIMEOUT));
+    // Nagle holds the body for one round trip, delayed ACK stretches that by the timer
+    assert!(nagle > plain + 0.019);
+    assert!((both - nagle - DELAYED_ACK_TIMEOUT).abs() < 0.001, "both {} vs Nagle alone {}", both, nagle);
+    let delayed_only = write_write_completion(false, Some(DELAYED_ACK_TIMEOUT));
+    assert!((delayed_only - plain).abs() < 1e-9);
+}
+
+#[test]
+fn path_mtu_is_the_narrowest_link_on_the_route() {
+    let mut sim = crate::theories::terrestrial_topology();
+    // New York -> Johannesburg is jumbo-framed, Johannesburg -> Pretoria is not
+    assert_eq!(sim.path_mtu(5, 1), Some(9000));
+    assert_eq!(sim.path_mtu(5, 100), Some(1500));
+    assert_eq!(sim.send_packet_pmtud(5, 100, 9000, PacketType::Standard), 6);
+}
+
+#[test]
+fn preloaded_content_is_a_hit_on_the_first_request() {
+    let mut cold = crate::theories::terrestrial_topology();
+    cold.verbose = false;
+    let miss = cold.send_cdn_request(100, 1, 7);
+    cold.run_simulation(f64::INFINITY);
+
+    let mut warm = crate::theories::terrestrial_topology();
+    warm.verbose = false;
+    warm.preload_cache(1, &[7]);
+    let hit = warm.send_cdn_request(100, 1, 7);
+    warm.run_simulation(f64::INFINITY);
+    assert_eq!((warm.cache_misses, warm.cache_hits), (0, 1));
+    let (miss, hit) = (cold.ttfb(miss).expect("miss answered"), warm.ttfb(hit).expect("hit answered"));
+    assert!(miss > hit + 0.05, "cold {} vs preloaded {}", miss, hit);
+}
+
+#[test]
+fn expensive_direct_link_diverts_to_a_slower_cheaper_path() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.set_routing_strategy(Box::new(CostRouting));
+    let route = |sim: &mut NetworkSimulation| {
+        let trace = sim.trace_packet(1, 2, 1500, PacketType::Standard).expect("London reachable");
+        (trace.hops.iter().map(|h| h.to).collect::<Vec<_>>(), trace.total_latency)
+    };
+    let (direct, direct_latency) = route(&mut sim);
+    assert_eq!(direct, vec![2]);
+
+    let cost = sim.get_link(1, 2).unwrap().cost;
+    sim.set_link_cost(1, 2, cost * 10.0);
+    let (diverted, diverted_latency) = route(&mut sim);
+    assert_eq!(diverted, vec![5, 2]);
+    assert!(diverted_latency > direct_latency);
+}
+
+#[test]
+fn resolve_then_connect_pays_dns_and_handshake_round_trips() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.resolve_then_connect(100, 1, 5);
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.connections.len(), 1);
+    let connection = sim.connections[0];
+    let dns_time = connection.dns_time.expect("resolved before connecting");
+    let (dns_rtt, server_rtt) = (sim.path_rtt(100, 1).unwrap(), sim.path_rtt(100, 5).unwrap());
+    assert!(dns_time >= dns_rtt);
+    assert!(connection.setup_time >= server_rtt);
+    assert!((connection.total_time() - dns_time - connection.setup_time).abs() < 1e-12);
+    assert!(connection.total_time() >= dns_rtt + server_rtt);
+}
+
+#[test]
+fn equal_time_events_pop_in_insertion_order() {
+    let mut sim = topogen::star(4);
+    sim.verbose = false;
+    sim.record_events = true;
+    let specs: Vec<SendSpec> = (0..4)
+        .flat_map(|_| [2, 3, 4].map(|source| (source, 1, 1500, PacketType::Standard, 0.0)))
+        .collect();
+    sim.send_batch(&specs);
+    let sequences: Vec<u64> = sim.event_queue.iter().map(|e| e.sequence).collect();
+    assert_eq!(sequences.iter().collect::<HashSet<_>>().len(), specs.len());
+    sim.run_simulation(f64::INFINITY);
+    let order: Vec<usize> = sim.event_log.iter()
+        .filter(|e| matches!(e.event_type, EventType::ScheduledSend(_)))
+        .map(|e| e.packet.id)
+        .collect();
+    assert_eq!(order.len(), specs.len());
+    assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "not FIFO: {:?}", order);
+}
+
+#[test]
+fn last_packet_of_bufferbloat_burst_queues_far_longer_than_the_first() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    crate::theories::TheoryTests::demonstrate_bufferbloat(&mut sim, 100, 1);
+    sim.run_simulation(f64::INFINITY);
+    let mut burst: Vec<_> = sim.completed_for_flow(100, 1).collect();
+    assert_eq!(burst.len(), 10);
+    burst.sort_by_key(|(p, _)| p.id);
+    let (first, last) = (&burst[0].0, &burst[9].0);
+    assert!(last.queuing_delay > 10.0 * first.queuing_delay.max(1e-3),
+            "first queued {} and last {}", first.queuing_delay, last.queuing_delay);
+    // The wait is almost all of the last packet's latency
+    assert!(last.queuing_delay > 0.5 * burst[9].1);
+}
+
+#[test]
+fn packet_to_self_is_delivered_as_loopback() {
+    let mut sim = two_hop();
+    assert!(sim.send_packet_ex(2, 2, 1500, PacketType::Standard));
+    sim.run_simulation(0.0);
+    assert!(sim.dropped_packets.is_empty());
+    assert_eq!(sim.completed_packets.len(), 1);
+    let (packet, latency) = &sim.completed_packets[0];
+    assert_eq!((packet.source_id, packet.destination_id), (2, 2));
+    assert!(*latency < 1e-9, "loopback took {}", latency);
+    assert_eq!(sim.links.iter().map(|l| l.bytes_sent).sum::<usize>(), 0);
+}
+
+#[test]
+fn second_stream_is_rejected_once_the_bottleneck_is_spoken_for() {
+    let mut sim = two_hop();
+    sim.links.iter_mut().filter(|l| (l.from, l.to) == (1, 3)).for_each(|l| l.bandwidth = 1_000_000_000.0);
+    assert!(sim.admit_stream(2, 3, 400_000_000.0));
+    assert!(sim.reserve_stream(2, 3, 400_000_000.0));
+    // 600 Mbps left on the hub's egress
+    assert!(!sim.admit_stream(2, 3, 700_000_000.0));
+    assert!(!sim.reserve_stream(2, 3, 700_000_000.0));
+    assert!(sim.admit_stream(2, 3, 500_000_000.0));
+}
+
+#[test]
+fn backbone_capacity_splits_intra_and_inter_region_links() {
+    let mut sim = two_hop();
+    for (id, region) in [(1, "Africa"), (2, "Africa"), (3, "Europe")] {
+        sim.servers.get_mut(&id).unwrap().region = region.to_string();
+    }
+    sim.links.iter_mut().filter(|l| (l.from, l.to) == (1, 3)).for_each(|l| l.bandwidth = 1e9);
+    let capacity = sim.backbone_capacity_by_region();
+    assert_eq!(capacity.len(), 3);
+    assert_eq!(capacity["Africa"], 2.0 * 10e9);
+    assert_eq!(capacity["Africa -> Europe"], 1e9);
+    assert_eq!(capacity["Europe -> Africa"], 10e9);
+}
+
+#[test]
+fn restored_snapshot_reruns_to_identical_result() {
+    let mut sim = two_hop();
+    sim.set_link_jitter(1, 3, 0.002);
+    sim.set_link_loss(1, 3, 0.1);
+    sim.generate_poisson_traffic(2, 3, 2_000.0, 0.5, &PacketSizeDistribution::Uniform { min: 64, max: 1500 });
+    sim.run_simulation(0.25);
+    let (midway, paused_at) = (sim.snapshot(), sim.current_time);
+    assert!(!sim.event_queue.is_empty());
+
+    let first = sim.run_simulation(f64::INFINITY);
+    let first_outcome = run_outcome(&sim);
+    sim.restore(&midway);
+    assert_eq!(sim.current_time, paused_at);
+    let replay = sim.run_simulation(f64::INFINITY);
+    assert_eq!(replay, first);
+    assert_eq!(run_outcome(&sim), first_outcome);
+}
+
+#[test]
+fn jhb_cpt_midpoint_lies_between_and_equidistant() {
+    let sim = crate::theories::terrestrial_topology();
+    let midpoint = sim.link_midpoint(1, 101);
+    let (jhb, cpt) = (sim.location_of(1).unwrap(), sim.location_of(101).unwrap());
+    let between = |value: f64, a: f64, b: f64| value > a.min(b) && value < a.max(b);
+    assert!(between(midpoint.latitude, jhb.latitude, cpt.latitude));
+    assert!(between(midpoint.longitude, jhb.longitude, cpt.longitude));
+    let (to_jhb, to_cpt) = (midpoint.distance_to(jhb), midpoint.distance_to(cpt));
+    assert!((to_jhb - to_cpt).abs() < 1e-3 * to_jhb, "{} vs {}", to_jhb, to_cpt);
+    assert!((to_jhb + to_cpt - jhb.distance_to(cpt)).abs() < 1e-3 * to_jhb);
+}
+
+#[test]
+fn ring_has_two_edge_disjoint_paths_between_opposite_nodes() {
+    let sim = topogen::ring(4);
+    let mut paths = sim.all_paths(1, 3, 4);
+    paths.sort();
+    assert_eq!(paths, vec![vec![1, 2, 3], vec![1, 4, 3]]);
+    assert_eq!(sim.edge_disjoint_paths(1, 3), 2);
+    // A single hop bound leaves nothing between non-neighbours
+    assert!(sim.all_paths(1, 3, 1).is_empty());
+}
+
+#[test]
+fn mathis_throughput_matches_formula_and_falls_with_loss() {
+    let mut sim = two_hop();
+    sim.links.iter_mut().for_each(|l| l.latency = 0.025);
+    // 100 ms RTT and 1% loss: 1460 * 8 / (0.1 * 0.1) bps
+    assert!((sim.mathis_throughput(2, 3, 1460, 0.01) - 1_168_000.0).abs() < 1e-6);
+    let ceilings: Vec<f64> = [0.001, 0.01, 0.05].map(|loss| sim.mathis_throughput(2, 3, TCP_MSS, loss)).to_vec();
+    assert!(ceilings.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", ceilings);
+    assert!(sim.mathis_throughput(2, 3, TCP_MSS, 0.0).is_infinite());
+}
+
+#[test]
+fn exponential_service_time_mean_converges() {
+    let mean_latency = |distribution: ServiceTimeDistribution| {
+        let mut sim = two_hop();
+        sim.servers.get_mut(&1).unwrap().service_time_distribution = Some(distribution);
+        sim.generate_poisson_traffic(2, 3, 20.0, 100.0, &PacketSizeDistribution::Fixed(1500));
+        sim.run_simulation(f64::INFINITY);
+        assert!(sim.completed_packets.len() > 1500);
+        sim.completed_packets.iter().map(|(_, l)| l).sum::<f64>() / sim.completed_packets.len() as f64
+    };
+    // At 4% utilization of the hub there's next to no waiting behind other packets
+    let observed = mean_latency(ServiceTimeDistribution::Exponential(0.002)) - mean_latency(ServiceTimeDistribution::Fixed(0.0));
+    assert!((observed - 0.002).abs() < 0.0002, "observed mean service {}", observed);
+
+    let mut sim = two_hop();
+    sim.servers.get_mut(&1).unwrap().service_time_distribution = Some(ServiceTimeDistribution::Exponential(0.002));
+    let draws: Vec<f64> = (0..20_000).map(|_| sim.service_time(1)).collect();
+    assert!((draws.iter().sum::<f64>() / draws.len() as f64 - 0.002).abs() < 0.0001);
+    assert!(draws.iter().all(|&t| t >= 0.0));
+}
+
+#[test]
+fn batched_requests_take_one_round_trip_and_chatty_ones_take_n() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    let rtt = sim.path_rtt(100, 5).unwrap();
+    let report = sim.compare_chatty_vs_batched(100, 5, 10);
+    assert_eq!(report.requests, 10);
+    assert!(report.batched_time >= rtt && report.batched_time < 1.2 * rtt, "batched {} vs RTT {}", report.batched_time, rtt);
+    assert!(report.chatty_time >= 10.0 * rtt && report.chatty_time < 12.0 * rtt, "chatty {} vs RTT {}", report.chatty_time, rtt);
+    // Both runs are rolled back
+    assert_eq!(sim.current_time, 0.0);
+    assert!(sim.completed_packets.is_empty());
+}
+
+#[test]
+fn transfer_cost_bills_only_the_metered_cable() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.set_link_cost_per_gb(1, 2, 2.5);
+    for _ in 0..1_000 {
+        sim.send_packet_ex(100, 2, 1000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    assert_eq!(sim.completed_packets.len(), 1_000);
+    // A megabyte crosses the Johannesburg -> London cable; the Pretoria leg is free
+    let metered_bytes = sim.get_link(1, 2).unwrap().bytes_sent;
+    assert_eq!(metered_bytes, 1_000_000);
+    assert!((sim.total_transfer_cost() - 1e-3 * 2.5).abs() < 1e-12);
+}
+
+#[test]
+fn transfer_cost_skips_bytes_lost_on_the_cable() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.set_link_cost_per_gb(1, 2, 2.5);
+    sim.set_link_loss(1, 2, 0.5);
+    for _ in 0..1_000 {
+        sim.send_packet_ex(100, 2, 1000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let link = sim.get_link(1, 2).unwrap();
+    let lost = sim.dropped_packets.iter().filter(|(_, reason)| *reason == DropReason::LinkLoss).count();
+    assert!(lost > 0);
+    assert_eq!(link.bytes_delivered, link.bytes_sent - lost * 1000);
+    assert_eq!(link.bytes_delivered, sim.completed_packets.len() * 1000);
+    assert!((sim.total_transfer_cost() - link.bytes_delivered as f64 / 1e9 * 2.5).abs() < 1e-12);
+}
+
+#[test]
+fn routed_detour_stretches_beyond_a_single_cable() {
+    let sim = topogen::ring(8);
+    // Neighbours ride one cable
+    assert!((sim.path_stretch(1, 2) - PATH_INEFFICIENCY_FACTOR).abs() < 1e-9);
+    // Opposite nodes go half way round instead of across: 4 sin(22.5 deg) = 1.53x the chord
+    let detour = sim.path_stretch(1, 5);
+    assert!(detour > 1.5 * PATH_INEFFICIENCY_FACTOR, "stretch {}", detour);
+    assert_eq!(sim.path_stretch(1, 1), 1.0);
+}
+
+#[test]
+fn stale_routes_drop_packets_until_convergence() {
+    let mut sim = slow_triangle();
+    sim.convergence_delay = 2.0;
+    let batch: Vec<SendSpec> = (0..40).map(|i| (2, 3, 1500, PacketType::Standard, i as f64 * 0.1)).collect();
+    sim.send_batch(&batch);
+    sim.run_simulation(0.58);
+    sim.set_link_down(2, 3);
+    sim.run_simulation(f64::INFINITY);
+
+    assert_eq!(sim.completed_packets.len() + sim.dropped_packets.len(), 40);
+    for (packet, reason) in &sim.dropped_packets {
+        assert_eq!(*reason, DropReason::LinkDown);
+        assert!(packet.created_at > 0.58 && packet.created_at < 2.58, "dropped at {}", packet.created_at);
+    }
+    assert_eq!(sim.dropped_packets.len(), 20);
+    for (packet, _) in &sim.completed_packets {
+        let direct = packet.path == vec![2, 3];
+        // Delivered before the failure, or detoured through 1 once routing caught up
+        assert!(if packet.created_at < 0.58 { direct } else { packet.created_at > 2.58 && !direct },
+                "created at {} took {:?}", packet.created_at, packet.path);
+    }
+}
+
+#[test]
+fn flow_and_type_iterators_return_only_matching_packets() {
+    let mut sim = topogen::star(4);
+    sim.verbose = false;
+    for (from, to) in [(2, 3), (2, 3), (3, 2), (2, 4), (4, 3)] {
+        sim.send_packet_ex(from, to, 1000, PacketType::Standard);
+    }
+    sim.send_cdn_request(2, 1, 7);
+    sim.run_simulation(f64::INFINITY);
+    let flow: Vec<_> = sim.completed_for_flow(2, 3).collect();
+    assert_eq!(flow.len(), 2);
+    assert!(flow.iter().all(|(p, _)| (p.source_id, p.destination_id) == (2, 3)));
+    assert_eq!(sim.completed_of_type(PacketType::Standard).count(), 5);
+    assert!(sim.completed_of_type(PacketType::CdnRequest).all(|(p, _)| p.packet_type == PacketType::CdnRequest));
+    assert_eq!(sim.completed_of_type(PacketType::CdnRequest).count(), 1);
+}
+
+/// `responders` leaves of a star answering leaf 2 at once with 32 segments
+/// each, through a 64 KB access buffer: (goodput bps, buffer drops).
+fn incast(responders: usize) -> (f64, usize) {
+    let mut sim = topogen::star(18);
+    sim.verbose = false;
+    sim.retransmit = Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 10 });
+    sim.set_link_queue_capacity(1, 2, Some(64 * 1024));
+    // Retransmitted segments are struck from dropped_packets once delivered, the log keeps them
+    sim.record_events = true;
+    for server in 3..3 + responders {
+        for _ in 0..32 {
+            sim.send_packet_ex(server, 2, TCP_MSS, PacketType::Standard);
+        }
+    }
+    sim.run_simulation(f64::INFINITY);
+    let (bytes, finished) = sim.completed_packets.iter()
+        .fold((0, 0.0), |(bytes, finished), (p, latency)| (bytes + p.size_bytes, f64::max(finished, p.created_at + latency)));
+    let drops = sim.event_log.iter().filter(|e| e.event_type == EventType::Dropped(1, DropReason::QueueFull)).count();
+    (bytes as f64 * 8.0 / finished, drops)
+}
+
+#[test]
+fn incast_goodput_collapses_as_responders_grow() {
+    let runs: Vec<(f64, usize)> = [1, 2, 4, 16].map(incast).to_vec();
+    // Up to two responses fit the buffer, after that every lost segment waits for an RTO
+    assert_eq!((runs[0].1, runs[1].1), (0, 0));
+    assert!(runs[1].0 > runs[0].0);
+    assert!(runs[2].1 > 0 && runs[3].1 > runs[2].1, "{:?}", runs);
+    assert!(runs[3].0 < runs[2].0 && runs[2].0 < runs[1].0 / 2.0, "{:?}", runs);
+}
+
+#[test]
+fn subsea_tag_totals_the_bytes_over_its_cables() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    for _ in 0..10 {
+        sim.send_packet_ex(100, 2, 1000, PacketType::Standard);
+    }
+    for _ in 0..5 {
+        sim.send_packet_ex(1, 5, 1000, PacketType::Standard);
+    }
+    sim.run_simulation(f64::INFINITY);
+    let subsea = sim.stats_by_tag("subsea");
+    assert_eq!(subsea.links, 6);
+    let cables = [(1, 5), (5, 1), (1, 2), (2, 1), (2, 5), (5, 2)];
+    assert_eq!(subsea.bytes_carried, cables.iter().map(|&(a, b)| sim.get_link(a, b).unwrap().bytes_sent).sum::<usize>());
+    assert_eq!(subsea.bytes_carried, 15_000);
+    assert_eq!(sim.stats_by_tag("access").bytes_carried, 10_000);
+    assert_eq!(sim.stats_by_tag("untagged"), LinkStats::default());
+}
+
+#[test]
+fn latency_matrix_is_square_with_zero_diagonal() {
+    let mut sim = crate::theories::terrestrial_topology();
+    assert_eq!(sim.node_order(), vec![1, 2, 5, 100, 101]);
+    let matrix = sim.latency_matrix();
+    assert_eq!(matrix.len(), 5);
+    assert!(matrix.iter().all(|row| row.len() == 5));
+    assert!((0..5).all(|i| matrix[i][i] == 0.0));
+    // Pretoria -> London goes via Johannesburg
+    let expected = sim.get_link(100, 1).unwrap().latency + sim.get_link(1, 2).unwrap().latency;
+    assert!((matrix[3][1] - expected).abs() < 1e-12);
+    assert_eq!(matrix[3][1], sim.path_latency(100, 2).unwrap());
+
+    // Without Johannesburg, Pretoria can't reach New York
+    sim.remove_server(1);
+    assert_eq!(sim.node_order(), vec![2, 5, 100, 101]);
+    assert!(sim.latency_matrix()[2][1].is_infinite());
+}
+
+#[test]
+fn only_packets_inside_the_spike_window_are_delayed() {
+    let mut sim = two_hop();
+    sim.add_latency_spike(2, 1, 200.0, 1.0, 2.0);
+    let batch: Vec<SendSpec> = [0.5, 1.5, 2.5].map(|offset| (2, 3, 1500, PacketType::Standard, offset)).to_vec();
+    sim.send_batch(&batch);
+    sim.run_simulation(f64::INFINITY);
+    let mut latencies: Vec<(f64, f64)> = sim.completed_packets.iter().map(|(p, l)| (p.created_at, *l)).collect();
+    latencies.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let [(_, before), (_, during), (_, after)] = latencies[..] else { panic!("expected three deliveries, got {:?}", latencies) };
+    assert!((before - after).abs() < 1e-12);
+    assert!((during - before - 0.2).abs() < 1e-9, "during {} vs before {}", during, before);
+}
+
+#[test]
+fn simulated_queuing_delay_converges_to_mm1() {
+    assert_eq!(mm1_mean_delay(500.0, 1000.0), 0.002);
+    assert!(mm1_mean_delay(1000.0, 1000.0).is_infinite());
+
+    let (bandwidth, mean_size, load) = (10_000_000.0, 1000, 0.5);
+    let mut sim = topogen::star(2);
+    sim.verbose = false;
+    sim.links.iter_mut().filter(|l| (l.from, l.to) == (1, 2)).for_each(|l| l.bandwidth = bandwidth);
+    // No fragmentation, so every packet is one exponential service time
+    sim.set_link_mtu(1, 2, usize::MAX);
+    let offered = load * bandwidth;
+    // Half the service time waiting: 0.8 ms at 1250 packets/s
+    let predicted = sim.predicted_queuing_delay(1, 2, offered, mean_size).unwrap();
+    assert!((predicted - 0.0008).abs() < 1e-12);
+    sim.generate_poisson_traffic(1, 2, offered / (mean_size * 8) as f64, 40.0, &PacketSizeDistribution::Exponential(mean_size));
+    sim.run_simulation(f64::INFINITY);
+    let delays: Vec<f64> = sim.completed_for_flow(1, 2).map(|(p, _)| p.queuing_delay).collect();
+    let simulated = delays.iter().sum::<f64>() / delays.len() as f64;
+    assert!((simulated - predicted).abs() < 0.1 * predicted, "simulated {} vs M/M/1 {}", simulated, predicted);
+}
+
+#[test]
+fn edge_cache_keys_on_content_id() {
+    let mut sim = crate::theories::terrestrial_topology();
+    sim.verbose = false;
+    sim.set_link_dedup(5, 1, true);
+    let fetch = |sim: &mut NetworkSimulation, content_id: u64| {
+        let (hits, misses) = (sim.cache_hits, sim.cache_misses);
+        sim.send_content(100, 1, content_id, 512, PacketType::CdnRequest);
+        sim.run_simulation(f64::INFINITY);
+        (sim.cache_hits - hits, sim.cache_misses - misses)
+    };
+    assert_eq!(fetch(&mut sim, 500), (0, 1));
+    assert_eq!(fetch(&mut sim, 500), (1, 0));
+    assert_eq!(fetch(&mut sim, 501), (0, 1));
+    assert_eq!(sim.servers[&1].cache, HashSet::from([500, 501]));
+    assert_eq!(sim.get_link(5, 1).unwrap().dedup_bytes_saved, 0);
+
+    // Refetched after eviction, the origin's repeat payload is deduplicated on the way
+    sim.servers.get_mut(&1).unwrap().cache.clear();
+    assert_eq!(fetch(&mut sim, 500), (0, 1));
+    assert!(sim.get_link(5, 1).unwrap().dedup_bytes_saved > 0);
+}
+
+#[test]
+fn one_way_shortcut_makes_the_round_trip_asymmetric() {
+    let mut sim = topogen::ring(4);
+    sim.verbose = false;
+    // A shortcut from 1 straight across to 3, with no way back along it
+    sim.connect_nodes(1, 3, 10_000_000_000.0);
+    let (neighbour, across) = (sim.send_cdn_request(1, 2, 1), sim.send_cdn_request(1, 3, 1));
+    sim.run_simulation(f64::INFINITY);
+
+    assert!(sim.is_path_symmetric(neighbour));
+    assert!(!sim.is_path_symmetric(across));
+    let (forward, back) = sim.round_trip_paths(across).expect("answered");
+    assert_eq!(forward, &[1, 3]);
+    assert_eq!(back.len(), 3);
+    assert_eq!((back[0], back[2]), (3, 1));
+}
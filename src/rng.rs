@@ -0,0 +1,62 @@
+/// Small seeded PRNG (SplitMix64) so stochastic runs are reproducible without
+/// pulling in an external crate.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform sample in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform sample between `min` and `max` inclusive, in either order.
+    pub fn range_usize(&mut self, min: usize, max: usize) -> usize {
+        let (low, high) = if min <= max { (min, max) } else { (max, min) };
+        match (high - low).checked_add(1) {
+            Some(span) => low + (self.next_u64() % span as u64) as usize,
+            None => self.next_u64() as usize,
+        }
+    }
+
+    /// Exponentially distributed sample with the given mean.
+    pub fn exponential(&mut self, mean: f64) -> f64 {
+        -mean * (1.0 - self.next_f64()).ln()
+    }
+
+    /// Normally distributed sample (Box-Muller transform).
+    pub fn normal(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let radius = (-2.0 * (1.0 - self.next_f64()).ln()).sqrt();
+        let angle = std::f64::consts::TAU * self.next_f64();
+        mean + std_dev * radius * angle.cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_usize_accepts_any_bounds() {
+        let mut rng = SimRng::new(7);
+        for _ in 0..1000 {
+            assert!((3..=9).contains(&rng.range_usize(9, 3)));
+            assert_eq!(rng.range_usize(5, 5), 5);
+            assert!(rng.range_usize(usize::MAX - 1, usize::MAX) >= usize::MAX - 1);
+        }
+        rng.range_usize(0, usize::MAX);
+    }
+}
@@ -1,4 +1,105 @@
-use crate::network_core::{NetworkSimulation, PacketType};
+use std::collections::HashSet;
+
+use crate::celestial::MOON;
+use crate::topogen;
+use crate::network_core::{
+    Client, DropReason, GeoLocation, NetworkSimulation, PacketType,
+    RetransmitPolicy, Server, ServerRole, TCP_MSS,
+};
+
+/// The five-node Johannesburg / New York / London network, with Pretoria and
+/// Cape Town as clients, that the terrestrial demo runs on.
+pub fn terrestrial_topology() -> NetworkSimulation {
+    let mut sim = NetworkSimulation::new();
+    
+    // Setup Nodes
+    sim.add_server(Server {
+        id: 1,
+        location: GeoLocation { latitude: -26.2041, longitude: 28.0473, name: "Johannesburg".to_string() },
+        processing_delay: 0.0005,
+        service_time_distribution: None,
+        bandwidth: 100_000_000_000.0,
+        cache: HashSet::new(),
+        role: ServerRole::Edge,
+        origin: Some(5),
+        as_number: 37100,
+        power_watts: 1_500.0,
+        region: "Africa".to_string(),
+    });
+    
+    sim.add_server(Server {
+        id: 5,
+        location: GeoLocation { latitude: 40.7128, longitude: -74.0060, name: "New York".to_string() },
+        processing_delay: 0.0006,
+        service_time_distribution: None,
+        bandwidth: 200_000_000_000.0,
+        cache: HashSet::new(),
+        role: ServerRole::Origin,
+        origin: None,
+        as_number: 174,
+        power_watts: 2_500.0,
+        region: "North America".to_string(),
+    });
+
+    sim.add_server(Server {
+        id: 2,
+        location: GeoLocation { latitude: 51.5074, longitude: -0.1278, name: "London".to_string() },
+        processing_delay: 0.0005,
+        service_time_distribution: None,
+        bandwidth: 150_000_000_000.0,
+        cache: HashSet::new(),
+        role: ServerRole::Origin,
+        origin: None,
+        as_number: 5459,
+        power_watts: 2_000.0,
+        region: "Europe".to_string(),
+    });
+
+    sim.add_client(Client {
+        id: 100,
+        location: GeoLocation { latitude: -25.7479, longitude: 28.2293, name: "Pretoria".to_string() },
+        region: "Africa".to_string(),
+    });
+
+    sim.add_client(Client {
+        id: 101,
+        location: GeoLocation { latitude: -33.9249, longitude: 18.4241, name: "Cape Town".to_string() },
+        region: "Africa".to_string(),
+    });
+
+    // Setup Links
+    let bandwidth = 10_000_000_000.0;
+    // Residential access: 1 Gbps up, 10 Gbps down
+    sim.connect_asymmetric(100, 1, 1_000_000_000.0, bandwidth);
+    sim.connect_asymmetric(101, 1, 1_000_000_000.0, bandwidth);
+    sim.connect_nodes(1, 5, bandwidth);   
+    sim.connect_nodes(5, 1, bandwidth);   
+
+    // JHB <-> London follows the West Africa Cable System up the Atlantic coast
+    let landing = |name: &str, latitude: f64, longitude: f64| GeoLocation { latitude, longitude, name: name.to_string() };
+    let mut wacs = vec![
+        landing("Yzerfontein", -33.3500, 18.1500),
+        landing("Luanda", -8.8390, 13.2894),
+        landing("Lagos", 6.4550, 3.3841),
+        landing("Sesimbra", 38.4445, -9.1015),
+    ];
+    sim.connect_nodes_via(1, 2, &wacs, bandwidth);
+    wacs.reverse();
+    sim.connect_nodes_via(2, 1, &wacs, bandwidth);
+    sim.connect_nodes(2, 5, bandwidth);
+    sim.connect_nodes(5, 2, bandwidth);
+
+    // Backbone carries jumbo frames; access links stay at the standard 1500-byte MTU
+    sim.set_link_mtu(1, 5, 9000);
+    sim.set_link_mtu(5, 1, 9000);
+
+    // Label links by kind so the results can be broken down per category
+    for (a, b, tag) in [(100, 1, "access"), (101, 1, "access"), (1, 5, "subsea"), (1, 2, "subsea"), (2, 5, "subsea")] {
+        sim.tag_link(a, b, tag);
+        sim.tag_link(b, a, tag);
+    }
+    sim
+}
 
 pub struct TheoryTests;
 
@@ -9,6 +110,7 @@ impl TheoryTests {
     pub fn demonstrate_bufferbloat(sim: &mut NetworkSimulation, source: usize, destination: usize) {
         println!("\n--- [THEORY] Queuing Theory & Bufferbloat ---");
         println!("Scenario: Sending a burst of 10 packets at once. Watch the last one cry.");
+        println!("Each 10MB packet is fragmented at the link MTU, so the queue fills with thousands of frames.");
         
         // Send 10 chunky packets in the same microsecond
         // [Sepedi]: Re romela diphakete tše lesome ka nako e tee. Ela hloko ya mafelelo ge e diega kudu.
@@ -16,6 +118,10 @@ impl TheoryTests {
             // 10MB packets to really clog the drain
             sim.send_packet_ex(source, destination, 10_000_000, PacketType::Standard);
         }
+        if let Some(first_hop) = sim.find_next_hop(source, destination) {
+            let queued = sim.link_queue_delay(source, first_hop).unwrap_or(0.0);
+            println!("Queue on {} -> {} right after the burst: {:.1} ms deep", sim.get_node_name(source), sim.get_node_name(first_hop), queued * 1000.0);
+        }
     }
 
     // 2. The "Round-Trip Tax" proof (Tefelo ya leeto la go ya le go boa)
@@ -37,21 +143,187 @@ impl TheoryTests {
 
     // Proving that if you can't beat physics, you cheat by moving the server
     // [Sepedi]: Ge melao ya tlhago e palelwa go fenyiwa, tharollo ke go tliša seba kgauswi le mošomiši
-    pub fn demonstrate_cdn_solution(sim: &mut NetworkSimulation, client_id: usize, origin_id: usize, edge_id: usize) {
+    // Returns the (origin, cold edge, warm edge) request ids, for `NetworkSimulation::ttfb`.
+    pub fn demonstrate_cdn_solution(sim: &mut NetworkSimulation, client_id: usize, origin_id: usize, edge_id: usize) -> (usize, usize, usize) {
         println!("\n--- [THEORY] Edge Computing / CDN Solution ---");
         println!("Scenario: Comparing a request to New York vs a request to a local Johannesburg Edge cache.");
         
         // Request 1: The long haul to NYC (Origin)
         // [Sepedi]: Kgopelo e romelwa go seba sa kgole, gomme e tšea nako e ntši
         println!("Request 1: {} -> {} (Origin)", sim.get_node_name(client_id), sim.get_node_name(origin_id));
-        sim.send_packet_ex(client_id, origin_id, 512, PacketType::CdnRequest);
+        let origin_request = sim.send_cdn_request(client_id, origin_id, 1);
 
-        // Request 2: The short hop to JHB (Edge)
+        // Request 2: The short hop to JHB (Edge), but the cache is cold so it misses
         // [Sepedi]: Kgopelo e romelwa go seba sa kgauswi, gomme karabo e fihla ka pela
-        println!("Request 2: {} -> {} (Edge Cache)", sim.get_node_name(client_id), sim.get_node_name(edge_id));
-        sim.send_packet_ex(client_id, edge_id, 512, PacketType::CdnRequest);
-        
+        println!("Request 2: {} -> {} (Edge Cache, cold)", sim.get_node_name(client_id), sim.get_node_name(edge_id));
+        let cold_request = sim.send_cdn_request(client_id, edge_id, 1);
+        sim.run_simulation(sim.current_time + 1.0);
+
+        // Request 3: Same content again, now served straight from the edge
+        println!("Request 3: {} -> {} (Edge Cache, warm)", sim.get_node_name(client_id), sim.get_node_name(edge_id));
+        let warm_request = sim.send_cdn_request(client_id, edge_id, 1);
+        sim.run_simulation(sim.current_time + 1.0);
+
+        for (label, request) in [("Origin", origin_request), ("Edge, cold", cold_request), ("Edge, warm", warm_request)] {
+            match sim.ttfb(request) {
+                Some(ttfb) => println!("TTFB {:<10} | {:>7.2} ms", label, ttfb * 1000.0),
+                None => println!("TTFB {:<10} | no response", label),
+            }
+        }
+
         // [Sepedi]: Bona phapang — seba sa kgauswi se araba ka pela ka gobane se kgauswi ka mmele.
-        println!("\nNotice the difference! The Edge response arrives almost instantly because it is physically closer.");
+        println!("\nNotice the difference! The first Edge request pays the origin trip once; after that the Edge response arrives almost instantly because it is physically closer.");
+        (origin_request, cold_request, warm_request)
+    }
+
+    // Proving that a small window wastes a fat, long pipe
+    pub fn demonstrate_bandwidth_delay_product(sim: &NetworkSimulation, source: usize, destination: usize) {
+        println!("\n--- [THEORY] Bandwidth-Delay Product ---");
+        let Some(bottleneck) = sim.bottleneck_bandwidth(source, destination) else { return };
+        let rtt = sim.path_rtt(source, destination).unwrap_or(0.0);
+        println!("Scenario: {} -> {} | bottleneck {:.2} Gbps | RTT {:.1} ms | BDP {:.1} MB",
+                 sim.get_node_name(source), sim.get_node_name(destination),
+                 bottleneck / 1_000_000_000.0, rtt * 1000.0, bottleneck * rtt / 8.0 / 1_000_000.0);
+
+        for window in [64 * 1024, 1024 * 1024, 16 * 1024 * 1024, 256 * 1024 * 1024] {
+            let ceiling = sim.max_window_throughput(source, destination, window);
+            let limit = if ceiling < bottleneck { "window-limited" } else { "bandwidth-limited" };
+            println!("Window {:>6} KB | {:>9.2} Mbps | {}", window / 1024, ceiling.min(bottleneck) / 1_000_000.0, limit);
+        }
+
+        let moon = earth_moon_link(bottleneck);
+        println!("Earth -> Moon ({:.2} s RTT) with a 64 KB window: {:.3} Mbps, no matter how big the antenna.",
+                 moon.path_rtt(1, 2).unwrap_or(0.0), moon.max_window_throughput(1, 2, 64 * 1024) / 1_000_000.0);
+    }
+
+    // Proving that without congestion control the event model overshoots what TCP can sustain under loss
+    pub fn demonstrate_mathis_throughput(sim: &mut NetworkSimulation, source: usize, destination: usize) {
+        println!("\n--- [THEORY] Mathis TCP Ceiling vs. Simulated Goodput ---");
+        println!("Scenario: 1,000 MSS-sized segments {} -> {} with retransmission, at rising link loss.",
+                 sim.get_node_name(source), sim.get_node_name(destination));
+
+        let verbose = sim.verbose;
+        sim.verbose = false;
+        sim.retransmit = Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 10 });
+        for loss in [0.001, 0.01, 0.05] {
+            sim.set_link_loss(source, destination, loss);
+            let start = sim.current_time;
+            for _ in 0..1_000 {
+                sim.send_packet_ex(source, destination, TCP_MSS, PacketType::Standard);
+            }
+            sim.run_simulation(start + 30.0);
+
+            let (bytes, finished) = sim.completed_for_flow(source, destination)
+                .filter(|(p, _)| p.created_at == start)
+                .fold((0, start), |(bytes, finished), (p, latency)| (bytes + p.size_bytes, f64::max(finished, start + latency)));
+            let simulated = if finished > start { bytes as f64 * 8.0 / (finished - start) } else { 0.0 };
+            println!("Loss {:>4.1}% | Mathis ceiling {:>8.2} Mbps | simulated (no congestion control) {:>8.2} Mbps",
+                     loss * 100.0, sim.mathis_throughput(source, destination, TCP_MSS, loss) / 1e6, simulated / 1e6);
+        }
+        sim.set_link_loss(source, destination, 0.0);
+        sim.retransmit = None;
+        sim.verbose = verbose;
+    }
+
+    // Proving the moon scenario's advice in the event engine: every serial round trip costs a full RTT
+    pub fn demonstrate_chatty_vs_batched(sim: &mut NetworkSimulation, client: usize, server: usize) {
+        println!("\n--- [THEORY] Chatty vs. Batched Requests ---");
+        let rtt = sim.path_rtt(client, server).unwrap_or(0.0);
+        println!("Scenario: {} fetching objects from {} (propagation RTT {:.1} ms), one at a time vs. all at once.",
+                 sim.get_node_name(client), sim.get_node_name(server), rtt * 1000.0);
+
+        let verbose = sim.verbose;
+        sim.verbose = false;
+        for requests in [1, 5, 20] {
+            let report = sim.compare_chatty_vs_batched(client, server, requests);
+            println!("{:>2} requests | chatty {:>8.1} ms ({:>4.1} RTTs) | batched {:>6.1} ms ({:>3.1} RTTs)",
+                     report.requests, report.chatty_time * 1000.0, report.chatty_time / rtt,
+                     report.batched_time * 1000.0, report.batched_time / rtt);
+        }
+        sim.verbose = verbose;
+    }
+
+    // Proving that synchronized replies overflow a shallow switch buffer and collapse goodput
+    pub fn demonstrate_incast(sim: &mut NetworkSimulation, client: usize, servers: &[usize]) {
+        const SEGMENTS: usize = 32;
+        const BUFFER_BYTES: usize = 64 * 1024;
+        println!("\n--- [THEORY] TCP Incast ---");
+        println!("Scenario: N servers answer {} at once with {} KB each; its access port buffers {} KB and lost segments wait for an RTO.",
+                 sim.get_node_name(client), SEGMENTS * TCP_MSS / 1000, BUFFER_BYTES / 1024);
+
+        let before = sim.snapshot();
+        let verbose = sim.verbose;
+        sim.verbose = false;
+        let mut counts: Vec<usize> = [1, 2, 4, 8, 16].into_iter().filter(|n| *n < servers.len()).collect();
+        counts.push(servers.len());
+        for responders in counts {
+            sim.restore(&before);
+            sim.verbose = false;
+            sim.retransmit = Some(RetransmitPolicy { rto_rtt_multiple: 3.0, max_retries: 10 });
+            let access_ports: Vec<usize> = sim.links.iter().filter(|l| l.to == client).map(|l| l.from).collect();
+            for from in access_ports {
+                sim.set_link_queue_capacity(from, client, Some(BUFFER_BYTES));
+            }
+
+            let start = sim.current_time;
+            for &server in &servers[..responders] {
+                for _ in 0..SEGMENTS {
+                    sim.send_packet_ex(server, client, TCP_MSS, PacketType::Standard);
+                }
+            }
+            sim.run_simulation(f64::INFINITY);
+
+            let (bytes, finished) = sim.completed_packets.iter()
+                .filter(|(p, _)| p.created_at >= start && p.destination_id == client)
+                .fold((0, start), |(bytes, finished), (p, latency)| (bytes + p.size_bytes, f64::max(finished, p.created_at + latency)));
+            let overflowed = sim.dropped_packets.iter()
+                .filter(|(p, reason)| p.created_at >= start && *reason == DropReason::QueueFull)
+                .count();
+            let elapsed = finished - start;
+            let goodput = if elapsed > 0.0 { bytes as f64 * 8.0 / elapsed } else { 0.0 };
+            println!("{:>2} servers | {:>3} buffer drops | {:>3} retransmissions | done in {:>6.1} ms | goodput {:>7.1} Mbps",
+                     responders, overflowed, sim.retransmissions, elapsed * 1000.0, goodput / 1e6);
+        }
+        sim.restore(&before);
+        sim.verbose = verbose;
+    }
+
+}
+
+/// Nodes 1 (Earth) and 2 (Moon) joined both ways by `bandwidth` links with
+/// the average Earth-Moon light time as their latency.
+fn earth_moon_link(bandwidth: f64) -> NetworkSimulation {
+    let mut sim = topogen::full_mesh(2);
+    sim.verbose = false;
+    for link in &mut sim.links {
+        link.latency = MOON.one_way_light_time(MOON.avg_dist_km);
+        link.bandwidth = bandwidth;
+    }
+    sim
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_ttfb_is_far_below_origin_ttfb() {
+        let mut sim = terrestrial_topology();
+        sim.verbose = false;
+        let (origin, cold, warm) = TheoryTests::demonstrate_cdn_solution(&mut sim, 100, 5, 1);
+        let ttfb = |request| sim.ttfb(request).expect("response arrived");
+        assert!(ttfb(warm) * 10.0 < ttfb(origin), "warm edge {} vs origin {}", ttfb(warm), ttfb(origin));
+        assert!(ttfb(warm) < ttfb(cold));
+    }
+
+    #[test]
+    fn small_window_starves_the_moon_link() {
+        let bandwidth = 1_000_000_000.0;
+        let moon = earth_moon_link(bandwidth);
+        let rtt = moon.path_rtt(1, 2).expect("linked");
+        assert!(rtt > 2.4);
+        let ceiling = moon.max_window_throughput(1, 2, 64 * 1024);
+        assert!((ceiling - 64.0 * 1024.0 * 8.0 / rtt).abs() < 1e-6);
+        assert!(ceiling < bandwidth / 1000.0, "{} bps", ceiling);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,36 @@
+//! Simulation time is plain `f64` seconds throughout; these helpers keep unit
+//! conversions and display formatting in one place.
+
+/// Seconds to milliseconds.
+pub fn ms(seconds: f64) -> f64 {
+    seconds * 1_000.0
+}
+
+/// Formats a duration in whichever of µs, ms, s or min reads most naturally,
+/// so a LAN hop and an Earth-Jupiter light time both print sensibly.
+pub fn format_duration(seconds: f64) -> String {
+    let magnitude = seconds.abs();
+    if magnitude < 0.001 {
+        format!("{:.2} µs", seconds * 1_000_000.0)
+    } else if magnitude < 1.0 {
+        format!("{:.2} ms", ms(seconds))
+    } else if magnitude < 120.0 {
+        format!("{:.2} s", seconds)
+    } else {
+        format!("{:.1} min", seconds / 60.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_picks_a_readable_unit() {
+        assert_eq!(format_duration(0.000_000_5), "0.50 µs");
+        assert_eq!(format_duration(0.05), "50.00 ms");
+        assert_eq!(format_duration(2.0), "2.00 s");
+        assert_eq!(format_duration(120.0), "2.0 min");
+        assert_eq!(ms(0.25), 250.0);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+
+use crate::network_core::{Client, GeoLocation, NetworkSimulation, Server, ServerRole};
+
+// Every generated node and link gets the same hardware.
+const PROCESSING_DELAY: f64 = 0.0005;
+const NODE_BANDWIDTH: f64 = 100_000_000_000.0;
+const POWER_WATTS: f64 = 1_000.0;
+const LINK_BANDWIDTH: f64 = 10_000_000_000.0;
+/// Generated layouts are drawn on a circle of this many degrees around (0, 0).
+const LAYOUT_RADIUS_DEGREES: f64 = 10.0;
+
+/// One hub (id 1) linked both ways to `n - 1` leaves around it.
+pub fn star(n: usize) -> NetworkSimulation {
+    build(|sim| {
+        add_server(sim, 1, "Hub", 0.0, 0.0);
+        for leaf in 2..=n {
+            let (lat, lon) = on_circle(leaf - 2, n - 1, LAYOUT_RADIUS_DEGREES);
+            add_server(sim, leaf, &format!("Leaf {}", leaf - 1), lat, lon);
+            connect_both(sim, 1, leaf);
+        }
+    })
+}
+
+/// `n` nodes in a cycle, each linked both ways to its two neighbours.
+pub fn ring(n: usize) -> NetworkSimulation {
+    build(|sim| {
+        add_circle(sim, n);
+        // Two nodes share one pair of links rather than two
+        let neighbours = match n {
+            0 | 1 => 0,
+            2 => 1,
+            _ => n,
+        };
+        for id in 1..=neighbours {
+            connect_both(sim, id, id % n + 1);
+        }
+    })
+}
+
+/// `n` nodes with a link from every node to every other: `n * (n - 1)` directed links.
+pub fn full_mesh(n: usize) -> NetworkSimulation {
+    build(|sim| {
+        add_circle(sim, n);
+        for from in 1..=n {
+            for to in (1..=n).filter(|&to| to != from) {
+                sim.connect_nodes(from, to, LINK_BANDWIDTH);
+            }
+        }
+    })
+}
+
+/// The k-ary fat tree used in datacenters: `(k/2)^2` core switches and `k`
+/// pods of `k/2` aggregation and `k/2` edge switches (all servers), plus
+/// `k^3/4` hosts as clients. Every adjacency is linked both ways, giving
+/// `3k^3/2` directed links. An odd `k` is rounded up.
+pub fn fat_tree(k: usize) -> NetworkSimulation {
+    let k = k.max(2).div_ceil(2) * 2;
+    let half = k / 2;
+    build(|sim| {
+        let mut last_id = 0;
+        let mut take_id = || {
+            last_id += 1;
+            last_id
+        };
+
+        let core: Vec<usize> = (0..half * half)
+            .map(|index| {
+                let id = take_id();
+                let (lat, lon) = on_circle(index, half * half, LAYOUT_RADIUS_DEGREES / 4.0);
+                add_server(sim, id, &format!("Core {}", index + 1), lat, lon);
+                id
+            })
+            .collect();
+
+        for pod in 0..k {
+            let (pod_lat, pod_lon) = on_circle(pod, k, LAYOUT_RADIUS_DEGREES);
+            let aggregation: Vec<usize> = (0..half)
+                .map(|index| {
+                    let id = take_id();
+                    add_server(sim, id, &format!("Agg {}.{}", pod + 1, index + 1), pod_lat * 0.7, pod_lon * 0.7 + index as f64 * 0.1);
+                    // Aggregation switch i reaches core switches i*k/2 .. (i+1)*k/2
+                    for &core_id in &core[index * half..(index + 1) * half] {
+                        connect_both(sim, id, core_id);
+                    }
+                    id
+                })
+                .collect();
+
+            for index in 0..half {
+                let edge = take_id();
+                add_server(sim, edge, &format!("Edge {}.{}", pod + 1, index + 1), pod_lat * 0.9, pod_lon * 0.9 + index as f64 * 0.1);
+                for &agg in &aggregation {
+                    connect_both(sim, edge, agg);
+                }
+                for host in 0..half {
+                    let id = take_id();
+                    sim.add_client(Client {
+                        id,
+                        location: GeoLocation {
+                            latitude: pod_lat,
+                            longitude: pod_lon + (index * half + host) as f64 * 0.05,
+                            name: format!("Host {}.{}.{}", pod + 1, index + 1, host + 1),
+                        },
+                        region: "Synthetic".to_string(),
+                    });
+                    connect_both(sim, id, edge);
+                }
+            }
+        }
+    })
+}
+
+/// Runs `populate` on a fresh simulation with logging off while it's built.
+fn build(populate: impl FnOnce(&mut NetworkSimulation)) -> NetworkSimulation {
+    let mut sim = NetworkSimulation::new();
+    let verbose = sim.verbose;
+    sim.verbose = false;
+    populate(&mut sim);
+    sim.verbose = verbose;
+    sim
+}
+
+/// Servers 1..=n evenly spaced around the layout circle.
+fn add_circle(sim: &mut NetworkSimulation, n: usize) {
+    for id in 1..=n {
+        let (lat, lon) = on_circle(id - 1, n, LAYOUT_RADIUS_DEGREES);
+        add_server(sim, id, &format!("Node {}", id), lat, lon);
+    }
+}
+
+fn on_circle(index: usize, count: usize, radius: f64) -> (f64, f64) {
+    let angle = std::f64::consts::TAU * index as f64 / count.max(1) as f64;
+    (radius * angle.sin(), radius * angle.cos())
+}
+
+fn add_server(sim: &mut NetworkSimulation, id: usize, name: &str, latitude: f64, longitude: f64) {
+    sim.add_server(Server {
+        id,
+        location: GeoLocation { latitude, longitude, name: name.to_string() },
+        processing_delay: PROCESSING_DELAY,
+        service_time_distribution: None,
+        bandwidth: NODE_BANDWIDTH,
+        cache: HashSet::new(),
+        role: ServerRole::Origin,
+        origin: None,
+        as_number: 0,
+        power_watts: POWER_WATTS,
+        region: "Synthetic".to_string(),
+    });
+}
+
+fn connect_both(sim: &mut NetworkSimulation, a: usize, b: usize) {
+    sim.connect_nodes(a, b, LINK_BANDWIDTH);
+    sim.connect_nodes(b, a, LINK_BANDWIDTH);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generators_produce_expected_node_and_link_counts() {
+        let counts = |sim: &NetworkSimulation| (sim.servers.len() + sim.clients.len(), sim.links.len());
+        assert_eq!(counts(&star(16)), (16, 30));
+        assert_eq!(counts(&ring(16)), (16, 32));
+        assert_eq!(counts(&ring(2)), (2, 2));
+        assert_eq!(counts(&full_mesh(16)), (16, 16 * 15));
+        let tree = fat_tree(4);
+        assert_eq!((tree.servers.len(), tree.clients.len()), (20, 16));
+        assert_eq!(tree.links.len(), 3 * 64 / 2);
+        assert!(tree.links.iter().all(|l| l.bandwidth == LINK_BANDWIDTH));
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::network_core::{GeoLocation, NetworkSimulation, Server, ServerRole};
+
+// Defaults for attributes GraphML exports commonly leave out.
+const DEFAULT_PROCESSING_DELAY: f64 = 0.0005;
+const DEFAULT_NODE_BANDWIDTH: f64 = 100_000_000_000.0;
+const DEFAULT_POWER_WATTS: f64 = 1_000.0;
+const DEFAULT_LINK_BANDWIDTH: f64 = 10_000_000_000.0;
+
+#[derive(Debug)]
+pub enum TopologyError {
+    Io(std::io::Error),
+    /// The document isn't well-formed enough to read.
+    Malformed(String),
+    /// A node has no latitude/longitude, so distances can't be computed.
+    MissingCoordinates { node: String },
+    /// An edge references a node id that was never declared.
+    UnknownNode { node: String },
+    InvalidNumber { attribute: String, value: String },
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyError::Io(e) => write!(f, "could not read topology: {}", e),
+            TopologyError::Malformed(reason) => write!(f, "malformed GraphML: {}", reason),
+            TopologyError::MissingCoordinates { node } => write!(f, "node '{}' is missing latitude/longitude", node),
+            TopologyError::UnknownNode { node } => write!(f, "edge references undeclared node '{}'", node),
+            TopologyError::InvalidNumber { attribute, value } => write!(f, "attribute '{}' has non-numeric value '{}'", attribute, value),
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl From<std::io::Error> for TopologyError {
+    fn from(e: std::io::Error) -> Self {
+        TopologyError::Io(e)
+    }
+}
+
+/// Reads a GraphML topology (e.g. from Mininet or the Internet Topology Zoo).
+/// See `parse_graphml` for the attributes understood.
+pub fn load_graphml(path: impl AsRef<Path>) -> Result<NetworkSimulation, TopologyError> {
+    parse_graphml(&fs::read_to_string(path)?)
+}
+
+/// Builds a simulation from GraphML text. Every node becomes a server, numbered
+/// from 1 in document order. Node attributes `latitude`/`lat`, `longitude`/`lon`
+/// and `name`/`label` are read, plus `region` (otherwise derived from the
+/// coordinates); edges take `bandwidth`/`bw`/`LinkSpeedRaw` in bps.
+/// Undirected edges (the GraphML default) become a link in each direction.
+/// Any other attribute is ignored.
+pub fn parse_graphml(text: &str) -> Result<NetworkSimulation, TopologyError> {
+    let mut keys: HashMap<String, String> = HashMap::new();
+    let mut directed = false;
+    let mut nodes: Vec<(String, HashMap<String, String>)> = Vec::new();
+    let mut edges: Vec<EdgeSpec> = Vec::new();
+    let mut open_data: Option<String> = None;
+    // The node or edge that `data` elements currently belong to
+    let mut owner: Option<Owner> = None;
+
+    for token in tokenize(text)? {
+        match token {
+            Token::Open { name, attrs, self_closing } => match name.as_str() {
+                "key" => {
+                    if let (Some(id), Some(attr_name)) = (attrs.get("id"), attrs.get("attr.name")) {
+                        keys.insert(id.clone(), attr_name.to_lowercase());
+                    }
+                }
+                "graph" => directed = attrs.get("edgedefault").is_some_and(|d| d == "directed"),
+                "node" => {
+                    let id = attrs.get("id").ok_or_else(|| TopologyError::Malformed("node without id".to_string()))?;
+                    nodes.push((id.clone(), HashMap::new()));
+                    owner = (!self_closing).then_some(Owner::Node(nodes.len() - 1));
+                }
+                "edge" => {
+                    let (Some(source), Some(target)) = (attrs.get("source"), attrs.get("target")) else {
+                        return Err(TopologyError::Malformed("edge without source/target".to_string()));
+                    };
+                    edges.push(EdgeSpec {
+                        source: source.clone(),
+                        target: target.clone(),
+                        directed: attrs.get("directed").map(|d| d == "true"),
+                        attrs: HashMap::new(),
+                    });
+                    owner = (!self_closing).then_some(Owner::Edge(edges.len() - 1));
+                }
+                "data" if !self_closing => open_data = attrs.get("key").cloned(),
+                _ => {}
+            },
+            Token::Close { name } => match name.as_str() {
+                "data" => open_data = None,
+                "node" | "edge" => owner = None,
+                _ => {}
+            },
+            Token::Text(value) => {
+                let (Some(key), Some(owner)) = (&open_data, owner) else { continue };
+                let attr = keys.get(key).cloned().unwrap_or_else(|| key.to_lowercase());
+                let target = match owner {
+                    Owner::Node(index) => &mut nodes[index].1,
+                    Owner::Edge(index) => &mut edges[index].attrs,
+                };
+                target.insert(attr, value.trim().to_string());
+            }
+        }
+    }
+
+    let mut sim = NetworkSimulation::new();
+    let verbose = sim.verbose;
+    sim.verbose = false;
+    let mut ids: HashMap<String, usize> = HashMap::new();
+
+    for (index, (graph_id, attrs)) in nodes.iter().enumerate() {
+        let latitude = number(attrs, &["latitude", "lat"])?;
+        let longitude = number(attrs, &["longitude", "lon", "lng"])?;
+        let (Some(latitude), Some(longitude)) = (latitude, longitude) else {
+            return Err(TopologyError::MissingCoordinates { node: graph_id.clone() });
+        };
+        let name = ["name", "label"].iter().find_map(|k| attrs.get(*k)).cloned().unwrap_or_else(|| graph_id.clone());
+        let location = GeoLocation { latitude, longitude, name };
+        let region = attrs.get("region").cloned().unwrap_or_else(|| location.continent().to_string());
+        let id = index + 1;
+        ids.insert(graph_id.clone(), id);
+        sim.add_server(Server {
+            id,
+            location,
+            processing_delay: DEFAULT_PROCESSING_DELAY,
+            service_time_distribution: None,
+            bandwidth: DEFAULT_NODE_BANDWIDTH,
+            cache: HashSet::new(),
+            role: ServerRole::Origin,
+            origin: None,
+            as_number: 0,
+            power_watts: DEFAULT_POWER_WATTS,
+            region,
+        });
+    }
+
+    for edge in &edges {
+        let lookup = |node: &String| ids.get(node).copied().ok_or_else(|| TopologyError::UnknownNode { node: node.clone() });
+        let (from, to) = (lookup(&edge.source)?, lookup(&edge.target)?);
+        let bandwidth = number(&edge.attrs, &["bandwidth", "bw", "linkspeedraw"])?.unwrap_or(DEFAULT_LINK_BANDWIDTH);
+        sim.connect_nodes(from, to, bandwidth);
+        if !edge.directed.unwrap_or(directed) {
+            sim.connect_nodes(to, from, bandwidth);
+        }
+    }
+
+    sim.verbose = verbose;
+    Ok(sim)
+}
+
+/// First of `names` present in `attrs`, parsed as a number.
+fn number(attrs: &HashMap<String, String>, names: &[&str]) -> Result<Option<f64>, TopologyError> {
+    let Some((name, value)) = names.iter().find_map(|n| attrs.get(*n).map(|v| (*n, v))) else { return Ok(None) };
+    value.parse::<f64>()
+        .map(Some)
+        .map_err(|_| TopologyError::InvalidNumber { attribute: name.to_string(), value: value.clone() })
+}
+
+struct EdgeSpec {
+    source: String,
+    target: String,
+    /// Per-edge override of the graph's `edgedefault`.
+    directed: Option<bool>,
+    attrs: HashMap<String, String>,
+}
+
+#[derive(Clone, Copy)]
+enum Owner {
+    Node(usize),
+    Edge(usize),
+}
+
+enum Token {
+    Open { name: String, attrs: HashMap<String, String>, self_closing: bool },
+    Close { name: String },
+    Text(String),
+}
+
+/// Just enough XML to read GraphML: tags, attributes and text. Comments,
+/// processing instructions and doctypes are skipped.
+fn tokenize(text: &str) -> Result<Vec<Token>, TopologyError> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let content = &rest[..start];
+        if !content.trim().is_empty() {
+            tokens.push(Token::Text(unescape(content)));
+        }
+        rest = &rest[start..];
+
+        if let Some(body) = rest.strip_prefix("<!--") {
+            let end = body.find("-->").ok_or_else(|| TopologyError::Malformed("unterminated comment".to_string()))?;
+            rest = &body[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').ok_or_else(|| TopologyError::Malformed("unterminated tag".to_string()))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::Close { name: name.trim().to_string() });
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attr_text) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        tokens.push(Token::Open { name: name.to_string(), attrs: parse_attributes(attr_text)?, self_closing });
+    }
+    Ok(tokens)
+}
+
+fn parse_attributes(mut text: &str) -> Result<HashMap<String, String>, TopologyError> {
+    let mut attrs = HashMap::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() { return Ok(attrs); }
+        let eq = text.find('=').ok_or_else(|| TopologyError::Malformed(format!("attribute without value near '{}'", text)))?;
+        let name = text[..eq].trim().to_string();
+        let after = text[eq + 1..].trim_start();
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| TopologyError::Malformed(format!("unquoted value for '{}'", name)))?;
+        let close = after[1..].find(quote).ok_or_else(|| TopologyError::Malformed(format!("unterminated value for '{}'", name)))?;
+        attrs.insert(name, unescape(&after[1..1 + close]));
+        text = &after[close + 2..];
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_loads_nodes_links_and_coordinates() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("topologies/southern_africa.graphml");
+        let sim = load_graphml(path).expect("fixture parses");
+        assert_eq!(sim.servers.len(), 5);
+        // Five undirected edges, one link each way
+        assert_eq!(sim.links.len(), 10);
+        let cape_town = &sim.servers[&2].location;
+        assert_eq!(cape_town.name, "Cape Town");
+        assert_eq!((cape_town.latitude, cape_town.longitude), (-33.9249, 18.4241));
+        let unlabelled = sim.get_link(2, 5).expect("edge without a speed");
+        assert_eq!(unlabelled.bandwidth, DEFAULT_LINK_BANDWIDTH);
+    }
+
+    #[test]
+    fn node_without_coordinates_is_rejected() {
+        let text = r#"<graphml><key attr.name="label" for="node" id="d0"/><graph>
+            <node id="a"><data key="d0">Nowhere</data></node>
+        </graph></graphml>"#;
+        match parse_graphml(text) {
+            Err(TopologyError::MissingCoordinates { node }) => assert_eq!(node, "a"),
+            other => panic!("expected missing coordinates, got {:?}", other.map(|sim| sim.servers.len())),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One trace record: (time offset in seconds, source id, destination id, size in bytes).
+pub type TraceRecord = (f64, usize, usize, usize);
+
+#[derive(Debug, Clone, Default)]
+pub struct TrafficTrace {
+    pub records: Vec<TraceRecord>,
+    /// Lines that couldn't be parsed and were left out.
+    pub skipped_lines: usize,
+}
+
+/// Reads a timestamped packet trace, one `time,src,dst,size` record per line.
+/// See `parse_traffic_trace` for the format.
+pub fn load_traffic_trace(path: impl AsRef<Path>) -> io::Result<TrafficTrace> {
+    Ok(parse_traffic_trace(&fs::read_to_string(path)?))
+}
+
+/// Parses comma-separated `time,src,dst,size` records. Blank lines, `#`
+/// comments and a leading `time,...` header are ignored; any other line that
+/// doesn't hold exactly four valid fields is counted in `skipped_lines` rather
+/// than aborting the load. Records are returned sorted by time.
+pub fn parse_traffic_trace(text: &str) -> TrafficTrace {
+    let mut trace = TrafficTrace::default();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.to_lowercase().starts_with("time") {
+            continue;
+        }
+        match parse_record(line) {
+            Some(record) => trace.records.push(record),
+            None => trace.skipped_lines += 1,
+        }
+    }
+    trace.records.sort_by(|a, b| a.0.total_cmp(&b.0));
+    trace
+}
+
+fn parse_record(line: &str) -> Option<TraceRecord> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [time, src, dst, size] = fields.as_slice() else { return None };
+    let time: f64 = time.parse().ok().filter(|t: &f64| t.is_finite() && *t >= 0.0)?;
+    Some((time, src.parse().ok()?, dst.parse().ok()?, size.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_lines_are_skipped_and_counted() {
+        let trace = parse_traffic_trace("time,src,dst,size\n# note\n0.2,1,2,100\n0.1,1,2\nx,1,2,100\n0.1,2,1,64\n");
+        assert_eq!(trace.records, vec![(0.1, 2, 1, 64), (0.2, 1, 2, 100)]);
+        assert_eq!(trace.skipped_lines, 2);
+    }
+}